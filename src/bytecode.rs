@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use crate::ir::{self, Kind, Operand, Program};
+use crate::value::Value;
+
+/*
+Bytecode
+
+The optimized IR compiled for the stack machine in `vm.rs`. Variables and
+temporaries live in numbered slots; operands are pushed, operators pop theirs
+and push the result. A temporary read only by the very next instruction, as
+its first operand, stays on the stack instead of going through a slot, which
+takes care of most of them.
+
+A `.bbc` file holds, with all integers little-endian:
+
+    "BBC" 1                   magic and format version
+    u32 slots                 no more than there are instructions
+    u32 count, constants      a type byte (0 int, 1 float, 2 double) and 8 value bytes
+    u32 count, strings        u32 length and UTF-8 bytes
+    u32 count, instructions   opcode byte, u32 operand for those that have one, u32 line
+*/
+
+const MAGIC: &[u8; 4] = b"BBC\x01";
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Opcode {
+    /// Pushes a constant from the pool.
+    Constant(u32),
+    Load(u32),
+    /// Pops into a variable, converting to float.
+    Store(u32),
+    /// Pops into a temporary, keeping the type.
+    Set(u32),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Negate,
+    Print,
+    PrintString(u32),
+    Input(u32),
+    Jump(u32),
+    JumpIfZero(u32),
+    Halt,
+}
+
+impl Opcode {
+    fn encode(&self) -> (u8, Option<u32>) {
+        match *self {
+            Opcode::Constant(index) => (0, Some(index)),
+            Opcode::Load(slot) => (1, Some(slot)),
+            Opcode::Store(slot) => (2, Some(slot)),
+            Opcode::Set(slot) => (3, Some(slot)),
+            Opcode::Add => (4, None),
+            Opcode::Sub => (5, None),
+            Opcode::Mul => (6, None),
+            Opcode::Div => (7, None),
+            Opcode::Eq => (8, None),
+            Opcode::Ne => (9, None),
+            Opcode::Lt => (10, None),
+            Opcode::Le => (11, None),
+            Opcode::Gt => (12, None),
+            Opcode::Ge => (13, None),
+            Opcode::Negate => (14, None),
+            Opcode::Print => (15, None),
+            Opcode::PrintString(index) => (16, Some(index)),
+            Opcode::Input(slot) => (17, Some(slot)),
+            Opcode::Jump(address) => (18, Some(address)),
+            Opcode::JumpIfZero(address) => (19, Some(address)),
+            Opcode::Halt => (20, None),
+        }
+    }
+
+    fn decode(byte: u8, reader: &mut Reader) -> Result<Opcode, String> {
+        Ok(match byte {
+            0 => Opcode::Constant(reader.u32()?),
+            1 => Opcode::Load(reader.u32()?),
+            2 => Opcode::Store(reader.u32()?),
+            3 => Opcode::Set(reader.u32()?),
+            4 => Opcode::Add,
+            5 => Opcode::Sub,
+            6 => Opcode::Mul,
+            7 => Opcode::Div,
+            8 => Opcode::Eq,
+            9 => Opcode::Ne,
+            10 => Opcode::Lt,
+            11 => Opcode::Le,
+            12 => Opcode::Gt,
+            13 => Opcode::Ge,
+            14 => Opcode::Negate,
+            15 => Opcode::Print,
+            16 => Opcode::PrintString(reader.u32()?),
+            17 => Opcode::Input(reader.u32()?),
+            18 => Opcode::Jump(reader.u32()?),
+            19 => Opcode::JumpIfZero(reader.u32()?),
+            20 => Opcode::Halt,
+            _ => return Err(format!("unknown opcode {byte}")),
+        })
+    }
+
+    fn from_op(op: ir::Op) -> Opcode {
+        match op {
+            ir::Op::Add => Opcode::Add,
+            ir::Op::Sub => Opcode::Sub,
+            ir::Op::Mul => Opcode::Mul,
+            ir::Op::Div => Opcode::Div,
+            ir::Op::Eq => Opcode::Eq,
+            ir::Op::Ne => Opcode::Ne,
+            ir::Op::Lt => Opcode::Lt,
+            ir::Op::Le => Opcode::Le,
+            ir::Op::Gt => Opcode::Gt,
+            ir::Op::Ge => Opcode::Ge,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Chunk {
+    pub slots: u32,
+    pub constants: Vec<Value>,
+    pub strings: Vec<String>,
+    /// Each instruction with the source line it came from.
+    pub code: Vec<(Opcode, u32)>,
+}
+
+pub fn compile(program: &Program) -> Chunk {
+    let mut compiler = Compiler {
+        chunk: Chunk { slots: 0, constants: vec![], strings: vec![], code: vec![] },
+        variables: HashMap::new(),
+        temps: HashMap::new(),
+        line: 0,
+    };
+    for variable in program.variables() {
+        compiler.variables.insert(variable, compiler.chunk.slots);
+        compiler.chunk.slots += 1;
+    }
+
+    let mut uses: HashMap<usize, usize> = HashMap::new();
+    for instruction in &program.instructions {
+        for operand in instruction.operands() {
+            if let Operand::Temp(temp) = operand {
+                *uses.entry(*temp).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut labels: HashMap<&str, u32> = HashMap::new();
+    let mut jumps: Vec<(usize, &str)> = vec![];
+    let mut on_stack: Option<usize> = None;
+    for (index, instruction) in program.instructions.iter().enumerate() {
+        compiler.line = instruction.line;
+        let kept = on_stack.take();
+
+        // leave the result on the stack when the next instruction pops it right away
+        let keep = instruction.dest().filter(|dest| {
+            uses.get(dest) == Some(&1)
+                && program.instructions.get(index + 1)
+                    .and_then(|next| next.operands().first().copied().cloned())
+                    == Some(Operand::Temp(*dest))
+        });
+
+        match &instruction.kind {
+            Kind::Binary { op, left, right, .. } => {
+                compiler.push(left, kept);
+                compiler.push(right, None);
+                compiler.emit(Opcode::from_op(*op));
+            },
+            Kind::Negate { operand, .. } => {
+                compiler.push(operand, kept);
+                compiler.emit(Opcode::Negate);
+            },
+            Kind::Store { variable, value } => {
+                compiler.push(value, kept);
+                compiler.emit(Opcode::Store(compiler.variables[variable]));
+            },
+            Kind::Input { variable } => compiler.emit(Opcode::Input(compiler.variables[variable])),
+            Kind::PrintString(text) => {
+                let index = match compiler.chunk.strings.iter().position(|string| string == text) {
+                    Some(index) => index,
+                    None => {
+                        compiler.chunk.strings.push(text.clone());
+                        compiler.chunk.strings.len() - 1
+                    },
+                };
+                compiler.emit(Opcode::PrintString(index as u32));
+            },
+            Kind::PrintValue(value) => {
+                compiler.push(value, kept);
+                compiler.emit(Opcode::Print);
+            },
+            Kind::Label(label) => {
+                labels.insert(label, compiler.chunk.code.len() as u32);
+            },
+            Kind::Jump(target) => {
+                jumps.push((compiler.chunk.code.len(), target));
+                compiler.emit(Opcode::Jump(0));
+            },
+            Kind::JumpIfZero { condition, target } => {
+                compiler.push(condition, kept);
+                jumps.push((compiler.chunk.code.len(), target));
+                compiler.emit(Opcode::JumpIfZero(0));
+            },
+        }
+
+        if let Some(dest) = instruction.dest() {
+            if keep.is_some() {
+                on_stack = Some(dest);
+            } else {
+                let slot = compiler.temp_slot(dest);
+                compiler.emit(Opcode::Set(slot));
+            }
+        }
+    }
+    compiler.emit(Opcode::Halt);
+
+    for (address, target) in jumps {
+        let destination = labels[target];
+        compiler.chunk.code[address].0 = match compiler.chunk.code[address].0 {
+            Opcode::Jump(_) => Opcode::Jump(destination),
+            _ => Opcode::JumpIfZero(destination),
+        };
+    }
+    compiler.chunk
+}
+
+struct Compiler {
+    chunk: Chunk,
+    variables: HashMap<String, u32>,
+    temps: HashMap<usize, u32>,
+    line: u32,
+}
+
+impl Compiler {
+    fn emit(&mut self, opcode: Opcode) {
+        self.chunk.code.push((opcode, self.line));
+    }
+
+    fn temp_slot(&mut self, temp: usize) -> u32 {
+        if let Some(slot) = self.temps.get(&temp) {
+            return *slot;
+        }
+        self.temps.insert(temp, self.chunk.slots);
+        self.chunk.slots += 1;
+        self.chunk.slots - 1
+    }
+
+    /// Pushes an operand, unless it is the temporary `kept` on the stack.
+    fn push(&mut self, operand: &Operand, kept: Option<usize>) {
+        match operand {
+            Operand::Temp(temp) if kept == Some(*temp) => {},
+            Operand::Temp(temp) => {
+                let slot = self.temp_slot(*temp);
+                self.emit(Opcode::Load(slot));
+            },
+            Operand::Variable(name) => self.emit(Opcode::Load(self.variables[name])),
+            Operand::Constant(value) => {
+                let index = match self.chunk.constants.iter().position(|constant| constant.literal() == value.literal()) {
+                    Some(index) => index,
+                    None => {
+                        self.chunk.constants.push(*value);
+                        self.chunk.constants.len() - 1
+                    },
+                };
+                self.emit(Opcode::Constant(index as u32));
+            },
+        }
+    }
+}
+
+impl Chunk {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(self.slots.to_le_bytes());
+
+        bytes.extend((self.constants.len() as u32).to_le_bytes());
+        for constant in &self.constants {
+            let (tag, value) = match constant {
+                Value::Int(value) => (0, i64::from(*value).to_le_bytes()),
+                Value::Float(value) => (1, (*value as f64).to_le_bytes()),
+                Value::Double(value) => (2, value.to_le_bytes()),
+            };
+            bytes.push(tag);
+            bytes.extend(value);
+        }
+
+        bytes.extend((self.strings.len() as u32).to_le_bytes());
+        for string in &self.strings {
+            bytes.extend((string.len() as u32).to_le_bytes());
+            bytes.extend(string.as_bytes());
+        }
+
+        bytes.extend((self.code.len() as u32).to_le_bytes());
+        for (opcode, line) in &self.code {
+            let (byte, operand) = opcode.encode();
+            bytes.push(byte);
+            if let Some(operand) = operand {
+                bytes.extend(operand.to_le_bytes());
+            }
+            bytes.extend(line.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads a `.bbc` file back, checking that every index in it is in range
+    /// so the VM can trust it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Chunk, String> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err("not a BASIC bytecode file".to_string());
+        }
+        let slots = reader.u32()?;
+
+        let mut constants = vec![];
+        for _ in 0..reader.u32()? {
+            let tag = reader.take(1)?[0];
+            let value: [u8; 8] = reader.take(8)?.try_into().unwrap();
+            constants.push(match tag {
+                0 => Value::Int(i32::try_from(i64::from_le_bytes(value)).map_err(|_| "int constant out of range")?),
+                1 => Value::Float(f64::from_le_bytes(value) as f32),
+                2 => Value::Double(f64::from_le_bytes(value)),
+                _ => return Err(format!("unknown constant type {tag}")),
+            });
+        }
+
+        let mut strings = vec![];
+        for _ in 0..reader.u32()? {
+            let length = reader.u32()? as usize;
+            let text = String::from_utf8(reader.take(length)?.to_vec()).map_err(|_| "string is not UTF-8")?;
+            strings.push(text);
+        }
+
+        let mut code = vec![];
+        for _ in 0..reader.u32()? {
+            let byte = reader.take(1)?[0];
+            let opcode = Opcode::decode(byte, &mut reader)?;
+            code.push((opcode, reader.u32()?));
+        }
+        if reader.position != bytes.len() {
+            return Err("trailing bytes after the code".to_string());
+        }
+        // every slot is used by an instruction, which also keeps a bad count
+        // from allocating more memory than the file could ever need
+        if slots as usize > code.len() {
+            return Err(format!("{slots} slots for {} instructions", code.len()));
+        }
+
+        let chunk = Chunk { slots, constants, strings, code };
+        for (opcode, _) in &chunk.code {
+            let valid = match *opcode {
+                Opcode::Constant(index) => (index as usize) < chunk.constants.len(),
+                Opcode::Load(slot) | Opcode::Store(slot) | Opcode::Set(slot) | Opcode::Input(slot) => slot < chunk.slots,
+                Opcode::PrintString(index) => (index as usize) < chunk.strings.len(),
+                Opcode::Jump(address) | Opcode::JumpIfZero(address) => (address as usize) < chunk.code.len(),
+                _ => true,
+            };
+            if !valid {
+                return Err(format!("operand of {:?} is out of range", opcode));
+            }
+        }
+        if chunk.code.last().map(|(opcode, _)| *opcode) != Some(Opcode::Halt) {
+            return Err("code does not end in halt".to_string());
+        }
+        Ok(chunk)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], String> {
+        let end = self.position.checked_add(count).filter(|end| *end <= self.bytes.len()).ok_or("file is truncated")?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
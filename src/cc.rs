@@ -0,0 +1,100 @@
+use std::env;
+use std::io::{self, Write};
+use std::process::Command;
+
+use compiler::mangle::Mangler;
+
+/*
+Building executables with the system C compiler
+
+Runs `$CC` (cc when unset) on the generated C with the flags in `$CFLAGS`
+(-O2 when unset), split on whitespace. The C compiler's diagnostics are
+passed on with the generated file's line numbers turned back into lines of
+the BASIC source, using the line each statement of the C came from:
+
+    /tmp/test1-1234.c:12:5: warning: ...   becomes   test1.bas:4: warning: ...
+
+Lines of the C that no statement produced (declarations, `return 0;`) keep
+their C location. Quoted names that were mangled on the way to C are put
+back as they are spelled in the BASIC.
+*/
+
+pub struct CCompiler {
+    command: String,
+    flags: Vec<String>,
+}
+
+impl CCompiler {
+    pub fn from_env() -> Self {
+        let command = env::var("CC").ok().filter(|command| !command.trim().is_empty());
+        let flags = env::var("CFLAGS").unwrap_or_else(|_| "-O2".to_string());
+        CCompiler {
+            command: command.unwrap_or_else(|| "cc".to_string()),
+            flags: flags.split_whitespace().map(str::to_string).collect(),
+        }
+    }
+
+    /// Compiles `c_file` to `executable`, writing the mapped diagnostics to stderr.
+    pub fn build(
+        &self,
+        c_file: &str,
+        executable: &str,
+        source: &str,
+        lines: &[u32],
+        names: &Mangler,
+    ) -> Result<(), String> {
+        let output = Command::new(&self.command)
+            .args(&self.flags)
+            .arg(c_file)
+            .arg("-o")
+            .arg(executable)
+            .output()
+            .map_err(|error| {
+                format!("could not run {}: {}; `interpret` runs the program without a C compiler", self.command, error)
+            })?;
+
+        let mut stderr = io::stderr().lock();
+        for text in [&output.stdout, &output.stderr] {
+            for line in String::from_utf8_lossy(text).lines() {
+                let mut line = map_diagnostic(line, c_file, source, lines);
+                for (mangled, name) in names.names() {
+                    // GCC and Clang quote with either ASCII or typographic quotes
+                    line = line.replace(&format!("'{mangled}'"), &format!("'{name}'"));
+                    line = line.replace(&format!("\u{2018}{mangled}\u{2019}"), &format!("\u{2018}{name}\u{2019}"));
+                }
+                writeln!(stderr, "{}", line).map_err(|error| format!("stderr: {error}"))?;
+            }
+        }
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!("{} failed ({})", self.command, output.status))
+        }
+    }
+}
+
+/// Rewrites a `file.c:line:column:` location to the BASIC line it came from.
+fn map_diagnostic(line: &str, c_file: &str, source: &str, lines: &[u32]) -> String {
+    let Some(rest) = line.strip_prefix(c_file).and_then(|rest| rest.strip_prefix(':')) else {
+        return line.to_string();
+    };
+    let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let Ok(number) = rest[..digits].parse::<usize>() else {
+        return line.to_string();
+    };
+    match lines.get(number.wrapping_sub(1)) {
+        Some(&basic) if basic != 0 => {
+            // drop the column, which means nothing in the BASIC
+            let rest = &rest[digits..];
+            let rest = match rest.strip_prefix(':') {
+                Some(column) if column.starts_with(|c: char| c.is_ascii_digit()) => {
+                    column.trim_start_matches(|c: char| c.is_ascii_digit())
+                },
+                _ => rest,
+            };
+            format!("{}:{}{}", source, basic, rest)
+        },
+        _ => line.to_string(),
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use crate::ir::{Kind, Program};
+
+/*
+Control-flow graph
+
+The IR is split into basic blocks: a block starts at the first instruction, at
+every label and after every jump, and covers the instructions `start..end`.
+A conditional jump gets a `True` edge to the next block (the condition held,
+so execution falls through) and a `False` edge to its target. An empty exit
+block is appended after the last instruction. `Cfg::to_dot` renders the graph
+for Graphviz, e.g. `compiler --emit=cfg-dot prog.bas | dot -Tsvg > cfg.svg`.
+*/
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum EdgeKind {
+    Fallthrough,
+    True,
+    False,
+    Jump,
+}
+
+#[derive(Clone)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<Edge>,
+}
+
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+    pub entry: usize,
+    pub exit: usize,
+}
+
+pub fn build(program: &Program) -> Cfg {
+    let instructions = &program.instructions;
+
+    let mut starts: Vec<usize> = vec![0];
+    for (index, instruction) in instructions.iter().enumerate() {
+        match instruction.kind {
+            Kind::Label(_) => starts.push(index),
+            Kind::Jump(_) | Kind::JumpIfZero { .. } => starts.push(index + 1),
+            _ => {}
+        }
+    }
+    starts.push(instructions.len());
+    starts.dedup();
+
+    let mut blocks: Vec<Block> = starts.windows(2)
+        .filter(|range| range[0] < range[1])
+        .map(|range| Block { start: range[0], end: range[1], successors: vec![] })
+        .collect();
+    let exit = blocks.len();
+    blocks.push(Block { start: instructions.len(), end: instructions.len(), successors: vec![] });
+
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    for (index, block) in blocks.iter().enumerate() {
+        if let Some(Kind::Label(label)) = instructions.get(block.start).map(|instruction| &instruction.kind) {
+            labels.insert(label, index);
+        }
+    }
+
+    for index in 0..exit {
+        let next = index + 1;
+        let last = &instructions[blocks[index].end - 1];
+        blocks[index].successors = match &last.kind {
+            Kind::Jump(label) => vec![Edge { target: labels[label.as_str()], kind: EdgeKind::Jump }],
+            Kind::JumpIfZero { target, .. } => vec![
+                Edge { target: next, kind: EdgeKind::True },
+                Edge { target: labels[target.as_str()], kind: EdgeKind::False },
+            ],
+            _ => vec![Edge { target: next, kind: EdgeKind::Fallthrough }],
+        };
+    }
+
+    Cfg { blocks, entry: 0, exit }
+}
+
+impl Cfg {
+    /// Which blocks can be reached from the entry, following only edges `feasible` accepts.
+    pub fn reachable(&self, feasible: &dyn Fn(usize, &Edge) -> bool) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut worklist = vec![self.entry];
+        while let Some(block) = worklist.pop() {
+            if reachable[block] {
+                continue;
+            }
+            reachable[block] = true;
+            for edge in &self.blocks[block].successors {
+                if feasible(block, edge) {
+                    worklist.push(edge.target);
+                }
+            }
+        }
+        reachable
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for (index, block) in self.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                predecessors[edge.target].push(index);
+            }
+        }
+        predecessors
+    }
+
+    /// Blocks reachable from the entry in reverse postorder.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut visited = vec![false; self.blocks.len()];
+        // (block, index of the next successor to visit)
+        let mut stack = vec![(self.entry, 0)];
+        visited[self.entry] = true;
+        while let Some((block, next)) = stack.pop() {
+            match self.blocks[block].successors.get(next) {
+                Some(edge) => {
+                    stack.push((block, next + 1));
+                    if !visited[edge.target] {
+                        visited[edge.target] = true;
+                        stack.push((edge.target, 0));
+                    }
+                },
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// The immediate dominator of every reachable block (the entry dominates
+    /// itself), using Cooper, Harvey and Kennedy's iterative algorithm.
+    pub fn dominators(&self) -> Vec<Option<usize>> {
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (index, block) in order.iter().enumerate() {
+            position[*block] = index;
+        }
+        let predecessors = self.predecessors();
+
+        let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        idom[self.entry] = Some(self.entry);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in order.iter().skip(1) {
+                let mut new_idom: Option<usize> = None;
+                for predecessor in &predecessors[*block] {
+                    if idom[*predecessor].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *predecessor,
+                        Some(current) => {
+                            let (mut left, mut right) = (current, *predecessor);
+                            while left != right {
+                                while position[left] > position[right] {
+                                    left = idom[left].unwrap();
+                                }
+                                while position[right] > position[left] {
+                                    right = idom[right].unwrap();
+                                }
+                            }
+                            left
+                        },
+                    });
+                }
+                if new_idom != idom[*block] {
+                    idom[*block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        idom
+    }
+
+    pub fn dominates(idom: &[Option<usize>], dominator: usize, mut block: usize) -> bool {
+        loop {
+            if block == dominator {
+                return true;
+            }
+            match idom[block] {
+                Some(parent) if parent != block => block = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    pub fn to_dot(&self, program: &Program) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = format!("B{}", index);
+            if index == self.entry {
+                label += " (entry)";
+            } else if index == self.exit {
+                label += " (exit)";
+            }
+            label += "\\l";
+            for instruction in &program.instructions[block.start..block.end] {
+                label += &escape(&instruction.to_string());
+                label += "\\l";
+            }
+            dot += &format!("    B{} [label=\"{}\"];\n", index, label);
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                let attributes = match edge.kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::True => " [label=\"true\"]",
+                    EdgeKind::False => " [label=\"false\"]",
+                    EdgeKind::Jump => " [style=dashed]",
+                };
+                dot += &format!("    B{} -> B{}{};\n", index, edge.target, attributes);
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
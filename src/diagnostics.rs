@@ -0,0 +1,37 @@
+use std::fmt;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub line: u32,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// What a compile reports, in the order of the lines they are about.
+pub type Diagnostics = Vec<Diagnostic>;
+
+impl Diagnostic {
+    pub fn warning(line: u32, message: String) -> Self {
+        Diagnostic { line, severity: Severity::Warning, message }
+    }
+
+    pub fn error(line: u32, message: String) -> Self {
+        Diagnostic { line, severity: Severity::Error, message }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "line {}: {}: {}", self.line, severity, self.message)
+    }
+}
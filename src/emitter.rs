@@ -1,41 +1,134 @@
-use std::{fs::File, io::Write, vec};
-use std::collections::HashSet;
+use std::vec;
+use std::collections::HashMap;
+use std::io::{self, Write};
 
-use crate::parse::Parser;
-use crate::{lex::TokenType, parse::Node};
+use crate::ir::{Kind, Operand, Program, Type};
+use crate::mangle::Mangler;
+
+/*
+C code generation from the IR
+
+Temporaries that are read exactly once are folded back into the expression
+that reads them, so `%0 = a + b`, `%1 = %0 * c`, `x = %1` comes out as
+`x = (a + b) * c;`. The pending expressions are written out as assignments to
+real C variables before anything that could change their value or merge
+control flow (a store, an INPUT, a label or a jump).
+
+Variables and labels go through `mangle.rs` on their way out, so a BASIC
+name can't be a C keyword or collide with the C library.
+
+As a library, the program becomes `int basic_run(basic_io *io)` in place of
+`main`, declared in a header along with `basic_io`. The variables live in
+`io->variables`, where the host can read them after the run, and PRINT and
+INPUT call the host's functions in `io`.
+*/
+
+/// C precedence levels, higher binds tighter.
+const ATOM: u8 = 16;
+const UNARY: u8 = 15;
+
+struct Expression {
+    text: String,
+    precedence: u8,
+}
 
 pub struct Emitter {
-    file_path: String,
     header: String,
     code: String,
-    ast: Node,
-    stack: Vec<Node>,
-    symbols: HashSet<String>,
+    program: Program,
+    uses: HashMap<usize, usize>,
+    pending: Vec<(usize, Expression)>,
     indent_space: i32,
-    needs_indent: bool
+    needs_indent: bool,
+    /// The BASIC line being emitted, and the one each line of `code` came from.
+    line: u32,
+    code_lines: Vec<u32>,
+    /// The .bas file `#line` directives name, when they are wanted.
+    directives: Option<String>,
+    directive_line: u32,
+    names: Mangler,
+    /// The name the header is included by, when emitting a library.
+    library: Option<String>,
 }
 
 impl Emitter {
-    pub fn new(file: String, ast: Node) -> Self {
+    pub fn new(program: Program) -> Self {
+        let mut uses: HashMap<usize, usize> = HashMap::new();
+        for instruction in &program.instructions {
+            for operand in instruction.operands() {
+                if let Operand::Temp(temp) = operand {
+                    *uses.entry(*temp).or_insert(0) += 1;
+                }
+            }
+        }
+
         Emitter {
-            file_path: file,
             header: String::from(""),
             code: String::from(""),
-            ast: ast,
-            stack: vec![],
-            symbols: HashSet::new(),
+            program,
+            uses,
+            pending: vec![],
             indent_space: 0,
-            needs_indent: true
+            needs_indent: true,
+            line: 0,
+            code_lines: vec![],
+            directives: None,
+            directive_line: 0,
+            names: Mangler::default(),
+            library: None,
         }
     }
 
-    pub fn print_tree(&mut self) {
-        self.header_line("#include <stdio.h>\n");
-        self.header_line("int main(void) {");
+    /// Puts `#line N "source"` before each statement, so debuggers and C
+    /// compiler messages refer to the BASIC source.
+    pub fn line_directives(&mut self, source: &str) {
+        self.directives = Some(source.replace('\\', "\\\\").replace('"', "\\\""));
+    }
+
+    /// Emits `basic_run` instead of `main`, including its declarations from
+    /// `header`, which `library_header` writes.
+    pub fn library(&mut self, header: &str) {
+        self.library = Some(header.to_string());
+    }
+
+    pub fn emit_program(&mut self) -> String {
+        let special = self.program.instructions.iter()
+            .flat_map(|instruction| instruction.operands())
+            .any(|operand| matches!(operand, Operand::Constant(value) if !value.is_finite()));
+        if special {
+            self.header_line("#include <math.h>");
+        }
+        match &self.library {
+            Some(header) => {
+                let include = format!("#include \"{}\"\n", header);
+                self.header_line(&include);
+                self.header_line("int basic_run(basic_io *io) {");
+            },
+            None => {
+                self.header_line("#include <stdio.h>\n");
+                self.header_line("int main(void) {");
+            },
+        }
         self.indent();
 
-        self.stack.push(self.ast.clone());
-        self.rprint_tree();
+        let variables = self.program.variables();
+        if self.library.is_some() && !variables.is_empty() {
+            self.function_header("basic_variables *v = &io->variables;");
+        }
+        if self.library.is_none() {
+            for variable in &variables {
+                let variable = self.names.mangle(variable);
+                self.function_header(&format!("float {};", variable));
+            }
+        }
+
+        let instructions = self.program.instructions.clone();
+        for instruction in &instructions {
+            self.line = instruction.line;
+            self.instruction(&instruction.kind);
+        }
+        self.flush();
+        self.line = 0;
 
         self.emit_line("");
         self.emit_line("return 0;");
@@ -43,168 +136,185 @@ impl Emitter {
         self.unindent();
         self.emit_line("}");
 
-        self.write_out();
+        format!("{}\n{}", self.header, self.code)
     }
 
-    fn rprint_tree(&mut self) {
-        let node = self.stack.last().unwrap().clone();
-
-        match node.token.kind {
-            TokenType::PRINT => {
-                if node.children.len() != 1 {
-                    unreachable!("Expected vector length equal to 1, got {}", node.children.len());
-                }
-
-                let child_node = &node.children[0];
-                if child_node.token.kind == TokenType::STRING {
-                    self.emit_line(&("printf(\"".to_owned() + &child_node.token.text + "\\n\");"));
-                } else {
-                    self.emit("printf(\"%.2f\\n\", (float)(");
-
-                    self.stack.push(child_node.clone());
-                    self.rprint_tree();
-                    
-                    self.emit_line("));");
-                }
-                self.stack.pop();
-                return;
-            },
-            TokenType::IF => {
-                self.emit("if (");
-                for child in &node.children {
-                    self.stack.push(child.clone());
-                    self.rprint_tree();
-                    self.stack.pop();
-                }
-                self.emit_line(") {");
-                self.indent();
-                return;
-            },
-            TokenType::ENDIF => {
-                self.unindent();
-                self.emit_line("}");
-                return;
-            },
-            TokenType::WHILE => {
-                self.emit("while (");
-
-                self.stack.push(node.children[0].clone());
-                self.rprint_tree();
-                self.stack.pop();
-
-                self.emit_line(") {");
-                self.indent();
-
-                for child in &node.children[1..] {
-                    self.stack.push(child.clone());
-                    self.rprint_tree();
-                    self.stack.pop();
-                }
+    /// `emit_program`, written to `out`.
+    pub fn write_program<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let program = self.emit_program();
+        out.write_all(program.as_bytes())
+    }
 
-                self.unindent();
-                self.emit_line("}");
-                return;
+    fn instruction(&mut self, kind: &Kind) {
+        match kind {
+            Kind::Binary { dest, op, left, right } => {
+                let precedence = match op.symbol() {
+                    "*" | "/" => 13,
+                    "+" | "-" => 12,
+                    "==" | "!=" => 9,
+                    _ => 10,
+                };
+                let left = self.operand(left);
+                let right = self.operand(right);
+                let text = format!(
+                    "{} {} {}",
+                    Emitter::parenthesize(left, precedence, false),
+                    op.symbol(),
+                    Emitter::parenthesize(right, precedence, true),
+                );
+                self.define(*dest, Expression { text, precedence });
             },
-            TokenType::LABEL => {
-                self.emit_line(&(node.token.text.clone() + ":"));
+            Kind::Negate { dest, operand } => {
+                let operand = Emitter::parenthesize(self.operand(operand), UNARY, false);
+                // `--2.0f` would be a decrement
+                let text = match operand.starts_with('-') {
+                    true => format!("-({})", operand),
+                    false => format!("-{}", operand),
+                };
+                self.define(*dest, Expression { text, precedence: UNARY });
             },
-            TokenType::GOTO => {
-                self.emit_line(&("goto ".to_owned() + &node.token.text.clone() + ";"));
+            Kind::Store { variable, value } => {
+                let value = self.operand(value);
+                let variable = self.variable(variable);
+                self.flush();
+                self.emit_line(&format!("{} = {};", variable, value.text));
             },
-            TokenType::LET => {
-                let first_node = node.children[0].clone();
-                let identifier = first_node.token.text;
-                if !self.symbols.contains(&identifier) {
-                    self.function_header(&format!("float {};", &identifier));
-                    self.symbols.insert(identifier.clone());
+            Kind::Input { variable } => {
+                let variable = self.variable(variable);
+                self.flush();
+                if self.library.is_some() {
+                    self.emit_line(&format!("io->input(io->context, &{});", variable));
+                    return;
                 }
-
-                self.emit(&format!("{} = ", identifier));
-
-                for child in &node.children[1..] {
-                    self.stack.push(child.clone());
-                    self.rprint_tree();
-                    self.stack.pop();
-                }
-                self.emit_line(";");
-                return;
-            },
-            TokenType::INPUT => {
-                let first_node = node.children[0].clone();
-                let identifier = first_node.token.text;
-                if !self.symbols.contains(&identifier) {
-                    self.function_header(&format!("float {};", &identifier));
-                    self.symbols.insert(identifier.clone());
-                }
-
-                self.emit_line(&("if (0 == scanf(\"%".to_owned() + "f\", &" + &identifier + ")) {"));
+                self.emit_line(&("if (0 == scanf(\"%".to_owned() + "f\", &" + &variable + ")) {"));
                 self.indent();
                 
-                self.emit_line(&(identifier + " = 0;"));
+                self.emit_line(&(variable.clone() + " = 0;"));
                 self.emit("scanf(\"%");
                 self.emit_line("*s\");");
                 
                 self.unindent();
                 self.emit_line("}");
-                return;
             },
-            TokenType::COMPARISON => {
-                for child in &node.children {
-                    let text = child.token.text.clone();
-                    if Parser::is_comparison(&text) {
-                        self.emit(&format!(" {} ", text));
-                    } else {
-                        self.stack.push(child.clone());
-                        self.rprint_tree();
-                        self.stack.pop();
-                    }
-                }
-                return;
+            Kind::PrintString(text) if self.library.is_some() => {
+                self.emit_line(&format!("io->print_string(io->context, \"{}\");", text));
             },
-            TokenType::EXPRESSION => {
-                for child in &node.children {
-                    let kind = child.token.kind;
-                    if kind == TokenType::PLUS || kind == TokenType::MINUS {
-                        self.emit(&format!(" {} ", child.token.text.clone()));
-                    } else {
-                        self.stack.push(child.clone());
-                        self.rprint_tree();
-                        self.stack.pop();
-                    }
-                }
-                return;
+            Kind::PrintString(text) => {
+                self.emit_line(&("printf(\"".to_owned() + text + "\\n\");"));
             },
-            TokenType::TERM => {
-                for child in &node.children {
-                    let kind = child.token.kind;
-                    if kind == TokenType::SLASH || kind == TokenType::ASTERISK {
-                        self.emit(&format!(" {} ", child.token.text.clone()));
-                    } else {
-                        self.stack.push(child.clone());
-                        self.rprint_tree();
-                        self.stack.pop();
-                    }
+            Kind::PrintValue(value) => {
+                let value = self.operand(value);
+                if self.library.is_some() {
+                    self.emit_line(&format!("io->print_number(io->context, (float)({}));", value.text));
+                } else {
+                    self.emit_line(&format!("printf(\"%.2f\\n\", (float)({}));", value.text));
                 }
-                return;
             },
-            TokenType::PLUS | TokenType::MINUS => {
-                self.emit(&format!("{}", node.token.text.clone()));
+            Kind::Label(label) => {
+                let label = self.names.mangle(label);
+                self.flush();
+                self.unindent();
+                self.emit_line(&format!("{}:", label));
+                self.indent();
+            },
+            Kind::Jump(label) => {
+                let label = self.names.mangle(label);
+                self.flush();
+                self.emit_line(&format!("goto {};", label));
+            },
+            Kind::JumpIfZero { condition, target } => {
+                let condition = self.operand(condition);
+                self.flush();
+                let target = self.names.mangle(target);
+                self.emit_line(&format!("if (!({})) goto {};", condition.text, target));
             },
-            TokenType::NUMBER | TokenType::IDENT => {
-                self.emit(&format!("{}", node.token.text.clone()));
-            }
-            _ => {}
         }
+    }
 
-        for child in &node.children {
-            self.stack.push(child.clone());
-            self.rprint_tree();
-            self.stack.pop();
+    fn operand(&mut self, operand: &Operand) -> Expression {
+        match operand {
+            Operand::Constant(value) => {
+                let text = value.literal();
+                let precedence = if text.starts_with(['-', '(']) { UNARY } else { ATOM };
+                Expression { text, precedence }
+            },
+            Operand::Variable(name) => Expression { text: self.variable(name), precedence: ATOM },
+            Operand::Temp(temp) => {
+                match self.pending.iter().position(|(pending, _)| pending == temp) {
+                    Some(index) => self.pending.remove(index).1,
+                    None => Expression { text: Emitter::temp_name(*temp), precedence: ATOM },
+                }
+            },
         }
     }
 
+    /// A BASIC variable as C, a member of `io->variables` in a library.
+    fn variable(&mut self, name: &str) -> String {
+        let name = self.names.mangle(name);
+        match self.library {
+            Some(_) => format!("v->{}", name),
+            None => name,
+        }
+    }
+
+    fn parenthesize(expression: Expression, precedence: u8, right: bool) -> String {
+        if expression.precedence < precedence || (right && expression.precedence == precedence) {
+            format!("({})", expression.text)
+        } else {
+            expression.text
+        }
+    }
+
+    fn define(&mut self, temp: usize, expression: Expression) {
+        if self.uses.get(&temp) == Some(&1) {
+            self.pending.push((temp, expression));
+        } else {
+            self.materialize(temp, expression);
+        }
+    }
+
+    fn flush(&mut self) {
+        for (temp, expression) in std::mem::take(&mut self.pending) {
+            self.materialize(temp, expression);
+        }
+    }
+
+    fn materialize(&mut self, temp: usize, expression: Expression) {
+        let kind = match self.program.temps[temp] {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Double => "double",
+        };
+        let name = Emitter::temp_name(temp);
+        self.function_header(&format!("{} {};", kind, name));
+        self.emit_line(&format!("{} = {};", name, expression.text));
+    }
+
+    /// The C names given to BASIC names that could not be used as they are.
+    pub fn names(&self) -> &Mangler {
+        &self.names
+    }
+
+    /// The BASIC line each line of the written file came from, 0 where none did.
+    pub fn source_lines(&self) -> Vec<u32> {
+        let header = self.header.matches('\n').count() + 1;
+        let mut lines = vec![0; header];
+        lines.extend(&self.code_lines);
+        lines
+    }
+
+    fn temp_name(temp: usize) -> String {
+        format!("t_{}", temp)
+    }
 
     fn emit(&mut self, line: &str) {
+        if let Some(source) = &self.directives {
+            if self.needs_indent && self.line != 0 && self.line != self.directive_line {
+                self.code += &format!("#line {} \"{}\"\n", self.line, source);
+                self.code_lines.push(0);
+                self.directive_line = self.line;
+            }
+        }
         if self.needs_indent {
             self.code += &format!("{}{}", " ".repeat(self.indent_space as usize), line);
             self.needs_indent = false;
@@ -216,6 +326,7 @@ impl Emitter {
     fn emit_line(&mut self, line: &str) {
         self.emit(line);
         self.code += "\n";
+        self.code_lines.push(self.line);
         self.needs_indent = true;
     }
     
@@ -228,11 +339,44 @@ impl Emitter {
         self.header += &format!("{}{}\n", " ".repeat(4), line);
     }
 
-    fn write_out(&mut self) {
-        let mut file = File::create(self.file_path.clone()).expect("Unable to open file!");
-        let _ok = write!(file, "{}", self.header).unwrap();
-        let _ok = write!(file, "{}", "\n").unwrap();
-        let _ok = write!(file, "{}", self.code).unwrap();
+    /// The header declaring `basic_run` and `basic_io`, for a library.
+    pub fn library_header(&mut self) -> Option<String> {
+        let header = self.library.clone()?;
+        let stem = header.rsplit_once('.').map_or(header.as_str(), |(stem, _)| stem);
+        let guard: String = stem.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        let mut text = format!("#ifndef BASIC_{0}_H\n#define BASIC_{0}_H\n\n", guard);
+
+        text += "/* The program's variables, as the last run left them. */\n";
+        text += "typedef struct basic_variables {\n";
+        let variables = self.program.variables();
+        for variable in &variables {
+            text += &format!("    float {};\n", self.names.mangle(variable));
+        }
+        if variables.is_empty() {
+            // C has no empty structs
+            text += "    char none;\n";
+        }
+        text += "} basic_variables;\n\n";
+
+        text += "typedef struct basic_io {\n";
+        text += "    basic_variables variables;\n";
+        text += "    /* passed to each of the functions below */\n";
+        text += "    void *context;\n";
+        text += "    /* PRINT \"text\", without the newline */\n";
+        text += "    void (*print_string)(void *context, const char *text);\n";
+        text += "    /* PRINT of a number */\n";
+        text += "    void (*print_number)(void *context, float value);\n";
+        text += "    /* INPUT: set *value to the number read, to 0 when the input is not\n";
+        text += "       a number, or leave it at the end of the input */\n";
+        text += "    void (*input)(void *context, float *value);\n";
+        text += "} basic_io;\n\n";
+
+        text += "/* Runs the program once, returning 0. */\n";
+        text += "int basic_run(basic_io *io);\n\n";
+        text += "#endif\n";
+        Some(text)
     }
 
     fn indent(&mut self) {
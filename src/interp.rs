@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use crate::diagnostics::Diagnostic;
+use crate::lex::TokenType;
+use crate::parse::Node;
+use crate::runtime::Console;
+use crate::value::{Fault, Value};
+
+/*
+Tree-walking interpreter
+
+Runs the parsed program directly, with the semantics of the C the emitter
+generates: variables are floats, expressions use C's types and conversions
+(see `value.rs`) and PRINT and INPUT behave like printf and scanf (see
+`runtime.rs`).
+
+GOTO can leave and enter IF and WHILE bodies. A GOTO unwinds to the top of the
+program, which then resumes at the label: the label's path through the tree
+says which statement to start at in each enclosing body, entering IF bodies
+without testing the condition and WHILE bodies before their first test, as
+the jumps in the generated C do.
+*/
+
+enum Control {
+    Next,
+    Goto(String),
+}
+
+pub struct Interpreter<R: BufRead, W: Write> {
+    console: Console<R, W>,
+    variables: HashMap<String, f32>,
+    /// Statement indices leading from the program to each label.
+    labels: HashMap<String, Vec<usize>>,
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Interpreter { console: Console::new(input, output), variables: HashMap::new(), labels: HashMap::new() }
+    }
+
+    pub fn run(&mut self, root: &Node) -> Result<(), Diagnostic> {
+        let mut path = vec![];
+        Interpreter::<R, W>::find_labels(&root.children, &mut path, &mut self.labels);
+
+        let mut resume: Vec<usize> = vec![];
+        while let Control::Goto(label) = self.block(&root.children, &resume)? {
+            resume = self.labels[&label].clone();
+        }
+        self.console.flush();
+        Ok(())
+    }
+
+    fn find_labels(statements: &[Node], path: &mut Vec<usize>, labels: &mut HashMap<String, Vec<usize>>) {
+        for (index, statement) in statements.iter().enumerate() {
+            path.push(index);
+            match statement.token.kind {
+                TokenType::LABEL => {
+                    labels.insert(statement.children[0].token.text.clone(), path.clone());
+                },
+                TokenType::IF | TokenType::WHILE => {
+                    Interpreter::<R, W>::find_labels(&statement.children[1..], path, labels);
+                },
+                _ => {}
+            }
+            path.pop();
+        }
+    }
+
+    /// Runs a list of statements, starting at the path `resume` if it is not empty.
+    fn block(&mut self, statements: &[Node], resume: &[usize]) -> Result<Control, Diagnostic> {
+        let first = resume.first().copied().unwrap_or(0);
+        for (index, statement) in statements.iter().enumerate().skip(first) {
+            let inner = match resume {
+                [_, inner @ ..] if index == first && !inner.is_empty() => Some(inner),
+                _ => None,
+            };
+            if let Control::Goto(label) = self.statement(statement, inner)? {
+                return Ok(Control::Goto(label));
+            }
+        }
+        Ok(Control::Next)
+    }
+
+    fn statement(&mut self, node: &Node, resume: Option<&[usize]>) -> Result<Control, Diagnostic> {
+        let line = node.token.line;
+        match node.token.kind {
+            TokenType::PRINT => {
+                let child = &node.children[0];
+                if child.token.kind == TokenType::STRING {
+                    self.console.print_string(&child.token.text);
+                } else {
+                    let value = self.expression(child, line)?;
+                    self.console.print_value(value.as_f64() as f32);
+                }
+            },
+            TokenType::IF => {
+                let body = &node.children[1..];
+                if resume.is_some() || self.condition(&node.children[0], line)? {
+                    return self.block(body, resume.unwrap_or(&[]));
+                }
+            },
+            TokenType::WHILE => {
+                let body = &node.children[1..];
+                let mut resume = resume;
+                while resume.is_some() || self.condition(&node.children[0], line)? {
+                    if let Control::Goto(label) = self.block(body, resume.take().unwrap_or(&[]))? {
+                        return Ok(Control::Goto(label));
+                    }
+                }
+            },
+            TokenType::GOTO => return Ok(Control::Goto(node.children[0].token.text.clone())),
+            TokenType::LET => {
+                let value = self.expression(&node.children[1], line)?;
+                self.variables.insert(node.children[0].token.text.clone(), value.as_f64() as f32);
+            },
+            TokenType::INPUT => {
+                if let Some(value) = self.console.input() {
+                    self.variables.insert(node.children[0].token.text.clone(), value);
+                }
+            },
+            _ => {}
+        }
+        Ok(Control::Next)
+    }
+
+    fn condition(&mut self, node: &Node, line: u32) -> Result<bool, Diagnostic> {
+        Ok(self.expression(node, line)?.as_f64() != 0.0)
+    }
+
+    fn expression(&mut self, node: &Node, line: u32) -> Result<Value, Diagnostic> {
+        let fault = |fault: Fault| Diagnostic::error(line, fault.to_string());
+        match node.token.kind {
+            TokenType::NUMBER => Ok(Value::parse(&node.token.text).unwrap()),
+            // a variable read before any assignment is 0, where the C would read garbage
+            TokenType::IDENT => Ok(Value::Float(self.variables.get(&node.token.text).copied().unwrap_or(0.0))),
+            TokenType::PLUS | TokenType::MINUS if node.children.len() == 1 => {
+                let value = self.expression(&node.children[0], line)?;
+                match node.token.kind {
+                    TokenType::MINUS => value.negate().ok_or(fault(Fault::IntegerOverflow)),
+                    _ => Ok(value),
+                }
+            },
+            TokenType::EXPRESSION | TokenType::TERM | TokenType::COMPARISON => {
+                let mut left = self.expression(&node.children[0], line)?;
+                for pair in node.children[1..].chunks(2) {
+                    let right = self.expression(&pair[1], line)?;
+                    left = match pair[0].token.kind {
+                        TokenType::PLUS | TokenType::MINUS | TokenType::ASTERISK | TokenType::SLASH => {
+                            Value::arithmetic(pair[0].token.kind, left, right).map_err(fault)?
+                        },
+                        _ => Value::compare(&pair[0].token.text, left, right).unwrap(),
+                    };
+                }
+                Ok(left)
+            },
+            _ => unreachable!("Unexpected expression node {0}", node.token.text),
+        }
+    }
+}
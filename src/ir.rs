@@ -0,0 +1,366 @@
+use std::fmt;
+
+use crate::lex::TokenType;
+use crate::parse::Node;
+use crate::value::{Fault, Value};
+
+/*
+Three-address intermediate representation
+
+The parsed program is lowered into a flat list of instructions. Every
+intermediate result of an expression goes into its own temporary, which is
+assigned exactly once and typed with the C type the expression would have
+(see `value.rs`), so backends reproduce the C semantics by construction.
+Structured statements become labels and jumps:
+
+    IF c THEN ... ENDIF         WHILE c REPEAT ... ENDWHILE
+        ifnot c goto endif_0    while_0:
+        ...                         ifnot c goto endwhile_0
+    endif_0:                        ...
+                                    goto while_0
+                                endwhile_0:
+
+Generated labels contain an underscore, which BASIC identifiers cannot.
+*/
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Type {
+    Int,
+    Float,
+    Double,
+}
+
+impl Type {
+    pub fn of(value: &Value) -> Type {
+        match value {
+            Value::Int(_) => Type::Int,
+            Value::Float(_) => Type::Float,
+            Value::Double(_) => Type::Double,
+        }
+    }
+
+    /// The type both operands of a binary operator are converted to.
+    pub fn common(left: Type, right: Type) -> Type {
+        match (left, right) {
+            (Type::Double, _) | (_, Type::Double) => Type::Double,
+            (Type::Float, _) | (_, Type::Float) => Type::Float,
+            _ => Type::Int,
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    pub fn from_token(kind: TokenType) -> Op {
+        match kind {
+            TokenType::PLUS => Op::Add,
+            TokenType::MINUS => Op::Sub,
+            TokenType::ASTERISK => Op::Mul,
+            TokenType::SLASH => Op::Div,
+            TokenType::EQEQ => Op::Eq,
+            TokenType::NOTEQ => Op::Ne,
+            TokenType::LT => Op::Lt,
+            TokenType::LTEQ => Op::Le,
+            TokenType::GT => Op::Gt,
+            TokenType::GTEQ => Op::Ge,
+            _ => unreachable!("Not a binary operator: {kind}"),
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        !matches!(self, Op::Add | Op::Sub | Op::Mul | Op::Div)
+    }
+
+    /// Computes the operator at compile time, see `Value::checked` and `Value::compare`.
+    pub fn evaluate(&self, left: Value, right: Value) -> Result<Value, Fault> {
+        match self {
+            Op::Add => Value::checked(TokenType::PLUS, left, right),
+            Op::Sub => Value::checked(TokenType::MINUS, left, right),
+            Op::Mul => Value::checked(TokenType::ASTERISK, left, right),
+            Op::Div => Value::checked(TokenType::SLASH, left, right),
+            _ => Ok(Value::compare(self.symbol(), left, right).unwrap()),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Operand {
+    Constant(Value),
+    Variable(String),
+    Temp(usize),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Kind {
+    Binary { dest: usize, op: Op, left: Operand, right: Operand },
+    Negate { dest: usize, operand: Operand },
+    Store { variable: String, value: Operand },
+    Input { variable: String },
+    PrintString(String),
+    PrintValue(Operand),
+    Label(String),
+    Jump(String),
+    JumpIfZero { condition: Operand, target: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub kind: Kind,
+    pub line: u32,
+}
+
+impl Instruction {
+    /// The temporary this instruction assigns, if any.
+    pub fn dest(&self) -> Option<usize> {
+        match self.kind {
+            Kind::Binary { dest, .. } | Kind::Negate { dest, .. } => Some(dest),
+            _ => None,
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match &self.kind {
+            Kind::Binary { left, right, .. } => vec![left, right],
+            Kind::Negate { operand, .. } => vec![operand],
+            Kind::Store { value, .. } => vec![value],
+            Kind::PrintValue(value) => vec![value],
+            Kind::JumpIfZero { condition, .. } => vec![condition],
+            _ => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match &mut self.kind {
+            Kind::Binary { left, right, .. } => vec![left, right],
+            Kind::Negate { operand, .. } => vec![operand],
+            Kind::Store { value, .. } => vec![value],
+            Kind::PrintValue(value) => vec![value],
+            Kind::JumpIfZero { condition, .. } => vec![condition],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    /// Result type of each temporary.
+    pub temps: Vec<Type>,
+}
+
+impl Program {
+    pub fn operand_type(&self, operand: &Operand) -> Type {
+        match operand {
+            Operand::Constant(value) => Type::of(value),
+            Operand::Variable(_) => Type::Float,
+            Operand::Temp(temp) => self.temps[*temp],
+        }
+    }
+
+    /// Every variable the program assigns or reads, in order of first appearance.
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = vec![];
+        for instruction in &self.instructions {
+            let mut names: Vec<&String> = vec![];
+            match &instruction.kind {
+                Kind::Store { variable, .. } | Kind::Input { variable } => names.push(variable),
+                _ => {}
+            }
+            for operand in instruction.operands() {
+                if let Operand::Variable(name) = operand {
+                    names.push(name);
+                }
+            }
+            for name in names {
+                if !variables.contains(name) {
+                    variables.push(name.clone());
+                }
+            }
+        }
+        variables
+    }
+}
+
+pub fn lower(root: &Node) -> Program {
+    let mut lowering = Lowering {
+        program: Program { instructions: vec![], temps: vec![] },
+        next_label: 0,
+        line: 0,
+    };
+    for statement in &root.children {
+        lowering.statement(statement);
+    }
+    lowering.program
+}
+
+struct Lowering {
+    program: Program,
+    next_label: usize,
+    line: u32,
+}
+
+impl Lowering {
+    fn push(&mut self, kind: Kind) {
+        self.program.instructions.push(Instruction { kind, line: self.line });
+    }
+
+    fn new_label(&mut self, prefix: &str) -> String {
+        self.next_label += 1;
+        format!("{}_{}", prefix, self.next_label - 1)
+    }
+
+    fn new_temp(&mut self, kind: Type) -> usize {
+        self.program.temps.push(kind);
+        self.program.temps.len() - 1
+    }
+
+    fn statement(&mut self, node: &Node) {
+        self.line = node.token.line;
+        match node.token.kind {
+            TokenType::PRINT => {
+                let child = &node.children[0];
+                if child.token.kind == TokenType::STRING {
+                    self.push(Kind::PrintString(child.token.text.clone()));
+                } else {
+                    let value = self.expression(child);
+                    self.push(Kind::PrintValue(value));
+                }
+            },
+            TokenType::IF => {
+                let end = self.new_label("endif");
+                let condition = self.expression(&node.children[0]);
+                self.push(Kind::JumpIfZero { condition, target: end.clone() });
+                for child in &node.children[1..] {
+                    self.statement(child);
+                }
+                self.line = node.token.line;
+                self.push(Kind::Label(end));
+            },
+            TokenType::WHILE => {
+                let head = self.new_label("while");
+                let end = self.new_label("endwhile");
+                self.push(Kind::Label(head.clone()));
+                let condition = self.expression(&node.children[0]);
+                self.push(Kind::JumpIfZero { condition, target: end.clone() });
+                for child in &node.children[1..] {
+                    self.statement(child);
+                }
+                self.line = node.token.line;
+                self.push(Kind::Jump(head));
+                self.push(Kind::Label(end));
+            },
+            TokenType::LABEL => self.push(Kind::Label(node.children[0].token.text.clone())),
+            TokenType::GOTO => self.push(Kind::Jump(node.children[0].token.text.clone())),
+            TokenType::LET => {
+                let value = self.expression(&node.children[1]);
+                self.push(Kind::Store { variable: node.children[0].token.text.clone(), value });
+            },
+            TokenType::INPUT => self.push(Kind::Input { variable: node.children[0].token.text.clone() }),
+            _ => {}
+        }
+    }
+
+    fn expression(&mut self, node: &Node) -> Operand {
+        match node.token.kind {
+            TokenType::NUMBER => Operand::Constant(Value::parse(&node.token.text).unwrap()),
+            TokenType::IDENT => Operand::Variable(node.token.text.clone()),
+            TokenType::PLUS | TokenType::MINUS if node.children.len() == 1 => {
+                let operand = self.expression(&node.children[0]);
+                if node.token.kind == TokenType::PLUS {
+                    return operand;
+                }
+                if let Operand::Constant(value) = &operand {
+                    if let Some(negated) = value.negate() {
+                        return Operand::Constant(negated);
+                    }
+                }
+                let dest = self.new_temp(self.program.operand_type(&operand));
+                self.push(Kind::Negate { dest, operand });
+                Operand::Temp(dest)
+            },
+            TokenType::EXPRESSION | TokenType::TERM | TokenType::COMPARISON => {
+                let mut left = self.expression(&node.children[0]);
+                for pair in node.children[1..].chunks(2) {
+                    let op = Op::from_token(pair[0].token.kind);
+                    let right = self.expression(&pair[1]);
+                    let kind = if op.is_comparison() {
+                        Type::Int
+                    } else {
+                        Type::common(self.program.operand_type(&left), self.program.operand_type(&right))
+                    };
+                    let dest = self.new_temp(kind);
+                    self.push(Kind::Binary { dest, op, left, right });
+                    left = Operand::Temp(dest);
+                }
+                left
+            },
+            _ => unreachable!("Unexpected expression node {0}", node.token.text),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Constant(value) => write!(f, "{}", value.literal()),
+            Operand::Variable(name) => write!(f, "{}", name),
+            Operand::Temp(temp) => write!(f, "%{}", temp),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            Kind::Binary { dest, op, left, right } => write!(f, "%{} = {} {} {}", dest, left, op.symbol(), right),
+            Kind::Negate { dest, operand } => write!(f, "%{} = -{}", dest, operand),
+            Kind::Store { variable, value } => write!(f, "{} = {}", variable, value),
+            Kind::Input { variable } => write!(f, "input {}", variable),
+            Kind::PrintString(text) => write!(f, "print \"{}\"", text),
+            Kind::PrintValue(value) => write!(f, "print {}", value),
+            Kind::Label(label) => write!(f, "{}:", label),
+            Kind::Jump(label) => write!(f, "goto {}", label),
+            Kind::JumpIfZero { condition, target } => write!(f, "ifnot {} goto {}", condition, target),
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instruction in &self.instructions {
+            match instruction.kind {
+                Kind::Label(_) => writeln!(f, "{}", instruction)?,
+                _ => writeln!(f, "    {}", instruction)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,29 @@
 use std::fmt;
 
+use crate::diagnostics::Diagnostic;
+
 pub struct Lexer {
     source: String,
     pub cur_char: char,
     pub cur_pos: u32,
+    pub cur_line: u32,
 }
 
 impl Lexer {
     pub fn new(source: String) -> Self {
-        let first_char = source.chars().nth(0).unwrap();
-        let lex = Lexer {
+        let first_char = source.chars().next().unwrap_or('\n');
+        Lexer {
             source: source + "\n",
             cur_char: first_char,
-            cur_pos: 0
-        };
-        lex
+            cur_pos: 0,
+            cur_line: 1
+        }
     }
 
     pub fn next_char(&mut self) {
+        if self.cur_char == '\n' {
+            self.cur_line += 1;
+        }
         self.cur_pos += 1;
         if self.cur_pos >= self.source.len().try_into().unwrap() {
             self.cur_char = '\0'; // EOF
@@ -30,50 +36,50 @@ impl Lexer {
         if self.cur_pos + 1 >= self.source.len().try_into().unwrap() {
             return '\0'; // EOF
         }
-        return self.source.as_bytes()[(self.cur_pos + 1) as usize] as char
+        self.source.as_bytes()[(self.cur_pos + 1) as usize] as char
     }
 
-    pub fn get_token(&mut self) -> Token {
+    pub fn get_token(&mut self) -> Result<Token, Diagnostic> {
         self.skip_whitespace();
         self.skip_comment();
 
         let token = match self.cur_char {
-            '+' => Token{text: self.cur_char.to_string(), kind: TokenType::PLUS},
-            '-' => Token{text: self.cur_char.to_string(), kind: TokenType::MINUS},
-            '*' => Token{text: self.cur_char.to_string(), kind: TokenType::ASTERISK},
-            '/' => Token{text: self.cur_char.to_string(), kind: TokenType::SLASH},
-            '\n' => Token{text: self.cur_char.to_string(), kind: TokenType::NEWLINE},
-            '\0' => Token{text: self.cur_char.to_string(), kind: TokenType::EOF},
+            '+' => Token{text: self.cur_char.to_string(), kind: TokenType::PLUS, line: self.cur_line},
+            '-' => Token{text: self.cur_char.to_string(), kind: TokenType::MINUS, line: self.cur_line},
+            '*' => Token{text: self.cur_char.to_string(), kind: TokenType::ASTERISK, line: self.cur_line},
+            '/' => Token{text: self.cur_char.to_string(), kind: TokenType::SLASH, line: self.cur_line},
+            '\n' => Token{text: self.cur_char.to_string(), kind: TokenType::NEWLINE, line: self.cur_line},
+            '\0' => Token{text: self.cur_char.to_string(), kind: TokenType::EOF, line: self.cur_line},
             '=' => {
                 if self.peek() == '=' {
                     self.next_char();
-                    Token{text: "==".to_string(), kind: TokenType::EQEQ}
+                    Token{text: "==".to_string(), kind: TokenType::EQEQ, line: self.cur_line}
                 } else {
-                    Token{text: "=".to_string(), kind: TokenType::EQ}
+                    Token{text: "=".to_string(), kind: TokenType::EQ, line: self.cur_line}
                 }
             },
             '>' => {
                 if self.peek() == '=' {
                     self.next_char();
-                    Token{text: ">=".to_string(), kind: TokenType::GTEQ}
+                    Token{text: ">=".to_string(), kind: TokenType::GTEQ, line: self.cur_line}
                 } else {
-                    Token{text: ">".to_string(), kind: TokenType::GT}
+                    Token{text: ">".to_string(), kind: TokenType::GT, line: self.cur_line}
                 }
             },
             '<' => {
                 if self.peek() == '=' {
                     self.next_char();
-                    Token{text: "<=".to_string(), kind: TokenType::LTEQ}
+                    Token{text: "<=".to_string(), kind: TokenType::LTEQ, line: self.cur_line}
                 } else {
-                    Token{text: "<".to_string(), kind: TokenType::LT}
+                    Token{text: "<".to_string(), kind: TokenType::LT, line: self.cur_line}
                 }
             },
             '!' => {
                 if self.peek() == '=' {
                     self.next_char();
-                    Token{text: "!=".to_string(), kind: TokenType::NOTEQ}
+                    Token{text: "!=".to_string(), kind: TokenType::NOTEQ, line: self.cur_line}
                 } else {
-                    unreachable!("Invalid input!");
+                    return Err(self.error("Expected = after !".to_string()));
                 }
             },
             '\"' => {
@@ -82,47 +88,125 @@ impl Lexer {
                 while self.cur_char != '\"' {
                     if self.cur_char == '\r' || self.cur_char == '\n' || self.cur_char == '\t' 
                     || self.cur_char == '\\' || self.cur_char == '%' {
-                        unreachable!("Invalid character in strung!");
+                        return Err(self.error("Invalid character in string".to_string()));
                     }
                     self.next_char();
                 }
                 let end_pos = self.cur_pos as usize;
                 let string: &str = &self.source[start_pos..end_pos];
-                Token{text: string.to_string(), kind: TokenType::STRING}
-            },
-            '0'..='9' => {
-                let start_pos = self.cur_pos as usize;
-                while self.peek().is_ascii_digit() {
-                    self.next_char();
-                }
-                if self.peek() == '.' {
-                    self.next_char();
-                    if !self.peek().is_ascii_digit() {
-                        unreachable!("Must have at least one digit after decimal place")
-                    }
-                    while self.peek().is_ascii_digit() {
-                        self.next_char();
-                    }    
-                }
-                let end_pos = (self.cur_pos + 1) as usize;
-                let string: &str = &self.source[start_pos..end_pos];
-                Token{text: string.to_string(), kind: TokenType::NUMBER}
+                Token{text: string.to_string(), kind: TokenType::STRING, line: self.cur_line}
             },
+            '0'..='9' => self.decimal_number()?,
+            '.' if self.peek().is_ascii_digit() => self.decimal_number()?,
+            '&' => self.radix_number()?,
             'a'..='z' | 'A'..='Z' => {
                 let start = self.cur_pos as usize;
-                while self.peek().is_alphanumeric() {
+                while self.peek().is_ascii_alphanumeric() {
                     self.next_char();
                 }
                 let end = (self.cur_pos + 1) as usize;
                 let substring: &str = &self.source[start..end];
 
                 let keyword = Token::check_keyword(substring);
-                Token{text: substring.to_string(), kind: keyword}
+                Token{text: substring.to_string(), kind: keyword, line: self.cur_line}
             }
-            _ => Token{text: self.cur_char.to_string(), kind: TokenType::BAD},
+            _ => Token{text: self.cur_char.to_string(), kind: TokenType::BAD, line: self.cur_line},
         };
         self.next_char();
-        token
+        Ok(token)
+    }
+
+    fn error(&self, message: String) -> Diagnostic {
+        Diagnostic::error(self.cur_line, message)
+    }
+
+    fn decimal_number(&mut self) -> Result<Token, Diagnostic> {
+        // digits ["." digits] [("e" | "E") ["+" | "-"] digits], or "." digits [exponent]
+        let start_pos = self.cur_pos as usize;
+        let mut is_float = self.cur_char == '.';
+        while self.peek().is_ascii_digit() {
+            self.next_char();
+        }
+        if !is_float && self.peek() == '.' {
+            self.next_char();
+            if !self.peek().is_ascii_digit() {
+                return Err(self.error("Must have at least one digit after decimal place".to_string()));
+            }
+            is_float = true;
+            while self.peek().is_ascii_digit() {
+                self.next_char();
+            }
+        }
+        if self.peek() == 'e' || self.peek() == 'E' {
+            self.next_char();
+            if self.peek() == '+' || self.peek() == '-' {
+                self.next_char();
+            }
+            if !self.peek().is_ascii_digit() {
+                return Err(self.error("Must have at least one digit in exponent".to_string()));
+            }
+            is_float = true;
+            while self.peek().is_ascii_digit() {
+                self.next_char();
+            }
+        }
+        let end_pos = (self.cur_pos + 1) as usize;
+        let string: &str = &self.source[start_pos..end_pos];
+
+        let text = if is_float {
+            let value: f64 = string.parse().unwrap();
+            if !value.is_finite() {
+                return Err(self.error(format!("Numeric literal {string} is out of range")));
+            }
+            Lexer::normalize_float(value)
+        } else {
+            match string.parse::<i64>() {
+                Ok(value) => Lexer::normalize_int(value),
+                Err(_) => return Err(self.error(format!("Numeric literal {string} is out of range"))),
+            }
+        };
+        Ok(Token{text, kind: TokenType::NUMBER, line: self.cur_line})
+    }
+
+    fn radix_number(&mut self) -> Result<Token, Diagnostic> {
+        // "&H" hexdigits | "&O" octdigits | "&B" bindigits
+        let radix = match self.peek() {
+            'H' | 'h' => 16,
+            'O' | 'o' => 8,
+            'B' | 'b' => 2,
+            _ => return Err(self.error(format!("Expected H, O or B after '&', got {}", self.peek()))),
+        };
+        self.next_char();
+        let start_pos = (self.cur_pos + 1) as usize;
+        while self.peek().is_ascii_alphanumeric() {
+            self.next_char();
+        }
+        let end_pos = (self.cur_pos + 1) as usize;
+        let digits: &str = &self.source[start_pos..end_pos];
+        if digits.is_empty() {
+            return Err(self.error(format!("Must have at least one digit after &{}", self.cur_char)));
+        }
+        match i64::from_str_radix(digits, radix) {
+            Ok(value) => Ok(Token{text: Lexer::normalize_int(value), kind: TokenType::NUMBER, line: self.cur_line}),
+            Err(_) => {
+                let prefix = self.source.as_bytes()[start_pos - 1] as char;
+                Err(self.error(format!("Invalid base {radix} literal &{prefix}{digits}")))
+            },
+        }
+    }
+
+    /// Spells an integer literal as a C `int`, or as a double when it doesn't fit one.
+    fn normalize_int(value: i64) -> String {
+        match i32::try_from(value) {
+            Ok(value) => value.to_string(),
+            Err(_) => Lexer::normalize_float(value as f64),
+        }
+    }
+
+    /// Spells a float so that both `str::parse::<f64>` and a C compiler read it
+    /// back as the same double, and so it is never mistaken for an integer.
+    pub fn normalize_float(value: f64) -> String {
+        format!("{:?}", value)
     }
 
     fn skip_whitespace(&mut self) {
@@ -143,6 +227,7 @@ impl Lexer {
 
 #[derive(PartialEq)]
 #[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenType {
     EOF,
 	NEWLINE,
@@ -228,6 +313,7 @@ impl fmt::Display for TokenType {
 pub struct Token {
     pub text: String,
     pub kind: TokenType,
+    pub line: u32,
 }
 
 impl Token {
@@ -0,0 +1,222 @@
+pub mod bytecode;
+pub mod cfg;
+pub mod diagnostics;
+pub mod emitter;
+pub mod interp;
+pub mod ir;
+pub mod lex;
+pub mod llvm;
+pub mod mangle;
+pub mod optimizer;
+pub mod parse;
+pub mod runtime;
+mod ssa;
+pub mod transpile;
+pub mod value;
+pub mod vm;
+pub mod wasm;
+pub mod wat;
+pub mod x86_64;
+
+use diagnostics::Diagnostics;
+use emitter::Emitter;
+use mangle::Mangler;
+use optimizer::PassManager;
+
+/*
+The compiler as a library
+
+`compile` takes BASIC source to any of the outputs, with everything the
+command line can set held in `Options`:
+
+    let options = Options::builder().target(Target::Wasm).opt_level(1).build()?;
+    let output = compile(&source, &options)?;
+
+The modules are public too, for tools that want the tokens, the AST or the IR
+on their way through, but `compile`, `Options` and `Output` are the interface
+meant to stay put.
+*/
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Target {
+    /// A C program, the default.
+    C,
+    /// `basic_run` in C, with `Output::header` declaring it.
+    CLibrary,
+    /// The control-flow graph of the optimized program, in Graphviz DOT.
+    CfgDot,
+    /// A bytecode file for the stack VM, the only output that isn't text.
+    Bytecode,
+    X86_64Asm,
+    LlvmIr,
+    Wasm,
+    Python,
+    JavaScript,
+    Rust,
+}
+
+pub struct Options {
+    target: Target,
+    passes: PassManager,
+    line_directives: Option<String>,
+    header: String,
+}
+
+impl Options {
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder {
+            target: Target::C,
+            level: 1,
+            toggles: vec![],
+            line_directives: None,
+            header: "prog.h".to_string(),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::builder().build().unwrap()
+    }
+}
+
+#[derive(Clone)]
+enum Toggle {
+    Enable,
+    Disable,
+    PrintAfter,
+}
+
+#[derive(Clone)]
+pub struct OptionsBuilder {
+    target: Target,
+    level: u8,
+    toggles: Vec<(Toggle, String)>,
+    line_directives: Option<String>,
+    header: String,
+}
+
+impl OptionsBuilder {
+    pub fn target(mut self, target: Target) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// 0, 1 or 2, as `-O`. 1 unless set, which keeps the exact results of `-O0`.
+    pub fn opt_level(mut self, level: u8) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn enable_pass(mut self, name: &str) -> Self {
+        self.toggles.push((Toggle::Enable, name.to_string()));
+        self
+    }
+
+    pub fn disable_pass(mut self, name: &str) -> Self {
+        self.toggles.push((Toggle::Disable, name.to_string()));
+        self
+    }
+
+    /// Dumps the AST or IR to stderr after the pass, or every pass for "all".
+    pub fn print_after(mut self, name: &str) -> Self {
+        self.toggles.push((Toggle::PrintAfter, name.to_string()));
+        self
+    }
+
+    /// Has the C say which line of `source` each statement comes from.
+    pub fn line_directives(mut self, source: &str) -> Self {
+        self.line_directives = Some(source.to_string());
+        self
+    }
+
+    /// The file name `Target::CLibrary` includes its header by.
+    pub fn header(mut self, name: &str) -> Self {
+        self.header = name.to_string();
+        self
+    }
+
+    /// Fails on a pass name that doesn't exist.
+    pub fn build(self) -> Result<Options, String> {
+        // passes named apply on top of the level, whatever the order
+        let mut passes = PassManager::new(self.level);
+        for (toggle, name) in &self.toggles {
+            match toggle {
+                Toggle::Enable => passes.enable(name)?,
+                Toggle::Disable => passes.disable(name)?,
+                Toggle::PrintAfter => passes.print_after(name)?,
+            }
+        }
+        Ok(Options { target: self.target, passes, line_directives: self.line_directives, header: self.header })
+    }
+}
+
+pub struct Output {
+    /// The program in the target language, or a bytecode file.
+    pub code: Vec<u8>,
+    /// For `Target::CLibrary`, the header declaring `basic_run`.
+    pub header: Option<String>,
+    /// The warnings, in line order.
+    pub diagnostics: Diagnostics,
+    /// For the C targets, the BASIC line each line of `code` came from, 0
+    /// where none did.
+    pub source_lines: Vec<u32>,
+    /// For the C targets, the BASIC names that were given other names in C.
+    pub names: Mangler,
+}
+
+impl Output {
+    fn new(code: impl Into<Vec<u8>>, diagnostics: Diagnostics) -> Self {
+        Output { code: code.into(), header: None, diagnostics, source_lines: vec![], names: Mangler::default() }
+    }
+
+    /// The code as text, for every target but bytecode.
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.code).ok()
+    }
+}
+
+/// Parses BASIC source, for use with the modules directly.
+pub fn parse(source: &str) -> Result<parse::Node, Diagnostics> {
+    let lexer = lex::Lexer::new(source.to_string());
+    parse::Parser::new(lexer).and_then(|mut parser| parser.program()).map_err(|error| vec![error])
+}
+
+pub fn compile(source: &str, options: &Options) -> Result<Output, Diagnostics> {
+    let ast = parse(source)?;
+
+    // the transpilers follow the source, so they take the AST as written
+    match options.target {
+        Target::Python => return Ok(Output::new(transpile::python(&ast), vec![])),
+        Target::JavaScript => return Ok(Output::new(transpile::javascript(&ast), vec![])),
+        Target::Rust => return Ok(Output::new(transpile::rust(&ast), vec![])),
+        _ => {},
+    }
+
+    let mut diagnostics = vec![];
+    let program = options.passes.run(ast, &mut diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+
+    let output = match options.target {
+        Target::CfgDot => Output::new(cfg::build(&program).to_dot(&program), diagnostics),
+        Target::Bytecode => Output::new(bytecode::compile(&program).to_bytes(), diagnostics),
+        Target::X86_64Asm => Output::new(x86_64::emit(&program), diagnostics),
+        Target::LlvmIr => Output::new(llvm::emit(&program), diagnostics),
+        Target::Wasm => Output::new(wasm::emit(&program), diagnostics),
+        _ => {
+            let mut emitter = Emitter::new(program);
+            if let Some(source) = &options.line_directives {
+                emitter.line_directives(source);
+            }
+            if options.target == Target::CLibrary {
+                emitter.library(&options.header);
+            }
+            let mut output = Output::new(emitter.emit_program(), diagnostics);
+            output.header = emitter.library_header();
+            output.source_lines = emitter.source_lines();
+            output.names = emitter.names().clone();
+            output
+        },
+    };
+    Ok(output)
+}
@@ -0,0 +1,280 @@
+use crate::ir::{Kind, Op, Operand, Program, Type};
+use crate::value::Value;
+
+/*
+LLVM IR generation from the IR
+
+Writes textual LLVM IR (`prog.ll`) that `clang prog.ll` compiles and links
+against the C library, so LLVM's optimizer and sanitizers can be used on the
+program. It computes what the generated C would: each temporary becomes an
+SSA value of its C type (`i32` for `Value::Int`, `float`, `double`), converted
+with sitofp/fpext/fptrunc like C's usual conversions, and PRINT and INPUT call
+printf and scanf, declared as external variadic functions. Pointers are the
+opaque `ptr` of LLVM 15 and later; LLVM 14 reads them with `-opaque-pointers`.
+
+Every variable gets an alloca in the entry block, initialized to 0, which
+`mem2reg` turns into SSA form. Labels start basic blocks named `L_<label>`;
+code after a jump that no label reaches gets a block of its own (`n<N>`), as
+LLVM requires every block to end in a terminator.
+*/
+
+pub fn emit(program: &Program) -> String {
+    let mut generator = Generator {
+        program,
+        text: String::new(),
+        strings: vec![],
+        next_value: 0,
+        next_block: 0,
+        terminated: false,
+    };
+    for instruction in &program.instructions {
+        generator.instruction(&instruction.kind);
+    }
+    generator.finish()
+}
+
+struct Generator<'a> {
+    program: &'a Program,
+    text: String,
+    strings: Vec<String>,
+    next_value: usize,
+    next_block: usize,
+    /// Whether the current block has already ended in a branch.
+    terminated: bool,
+}
+
+fn type_name(kind: Type) -> &'static str {
+    match kind {
+        Type::Int => "i32",
+        Type::Float => "float",
+        Type::Double => "double",
+    }
+}
+
+/// LLVM spells floating-point constants of both widths as the hex bits of a
+/// double, which keeps infinities, NaN and -0.0 exact.
+fn constant(value: Value) -> String {
+    match value {
+        Value::Int(value) => value.to_string(),
+        Value::Float(value) => format!("0x{:016X}", (value as f64).to_bits()),
+        Value::Double(value) => format!("0x{:016X}", value.to_bits()),
+    }
+}
+
+impl Generator<'_> {
+    fn line(&mut self, line: &str) {
+        self.text += "  ";
+        self.text += line;
+        self.text += "\n";
+    }
+
+    fn value(&mut self) -> String {
+        self.next_value += 1;
+        format!("%x{}", self.next_value - 1)
+    }
+
+    fn block_name(&mut self) -> String {
+        self.next_block += 1;
+        format!("n{}", self.next_block - 1)
+    }
+
+    fn start_block(&mut self, name: &str) {
+        if !self.terminated {
+            self.line(&format!("br label %{}", name));
+        }
+        self.text += &format!("{}:\n", name);
+        self.terminated = false;
+    }
+
+    fn branch(&mut self, line: &str) {
+        self.line(line);
+        self.terminated = true;
+    }
+
+    /// A pointer to the start of a string constant, for printf and scanf.
+    fn string(&mut self, text: &str) -> String {
+        let index = match self.strings.iter().position(|string| string == text) {
+            Some(index) => index,
+            None => {
+                self.strings.push(text.to_string());
+                self.strings.len() - 1
+            },
+        };
+        format!("ptr @.str.{}", index)
+    }
+
+    /// An operand converted to `kind`, as a value that can follow its type name.
+    fn load(&mut self, operand: &Operand, kind: Type) -> String {
+        let from = self.program.operand_type(operand);
+        let value = match operand {
+            Operand::Constant(value) => {
+                return constant(match kind {
+                    Type::Int => *value,
+                    Type::Float => value.as_float(),
+                    Type::Double => Value::Double(value.as_f64()),
+                });
+            },
+            Operand::Variable(name) => {
+                let value = self.value();
+                self.line(&format!("{} = load float, ptr %v_{}", value, name));
+                value
+            },
+            Operand::Temp(temp) => format!("%t{}", temp),
+        };
+        let conversion = match (from, kind) {
+            _ if from == kind => return value,
+            (Type::Int, _) => "sitofp",
+            (Type::Float, Type::Double) => "fpext",
+            (Type::Double, Type::Float) => "fptrunc",
+            _ => unreachable!("Cannot convert {:?} to {:?}", from, kind),
+        };
+        let converted = self.value();
+        self.line(&format!("{} = {} {} {} to {}", converted, conversion, type_name(from), value, type_name(kind)));
+        converted
+    }
+
+    fn instruction(&mut self, kind: &Kind) {
+        if self.terminated && !matches!(kind, Kind::Label(_)) {
+            let block = self.block_name();
+            self.start_block(&block);
+        }
+        match kind {
+            Kind::Binary { dest, op, left, right } => {
+                let operands = Type::common(self.program.operand_type(left), self.program.operand_type(right));
+                let left = self.load(left, operands);
+                let right = self.load(right, operands);
+                let name = type_name(operands);
+                if op.is_comparison() {
+                    let truth = self.value();
+                    let predicate = Generator::predicate(*op, operands);
+                    let compare = if operands == Type::Int { "icmp" } else { "fcmp" };
+                    self.line(&format!("{} = {} {} {} {}, {}", truth, compare, predicate, name, left, right));
+                    self.line(&format!("%t{} = zext i1 {} to i32", dest, truth));
+                } else {
+                    let instruction = match (op, operands) {
+                        (Op::Add, Type::Int) => "add",
+                        (Op::Sub, Type::Int) => "sub",
+                        (Op::Mul, Type::Int) => "mul",
+                        (Op::Div, Type::Int) => "sdiv",
+                        (Op::Add, _) => "fadd",
+                        (Op::Sub, _) => "fsub",
+                        (Op::Mul, _) => "fmul",
+                        (Op::Div, _) => "fdiv",
+                        _ => unreachable!("Not an arithmetic operator: {}", op.symbol()),
+                    };
+                    self.line(&format!("%t{} = {} {} {}, {}", dest, instruction, name, left, right));
+                }
+            },
+            Kind::Negate { dest, operand } => {
+                let kind = self.program.temps[*dest];
+                let operand = self.load(operand, kind);
+                match kind {
+                    Type::Int => self.line(&format!("%t{} = sub i32 0, {}", dest, operand)),
+                    _ => self.line(&format!("%t{} = fneg {} {}", dest, type_name(kind), operand)),
+                }
+            },
+            Kind::Store { variable, value } => {
+                let value = self.load(value, Type::Float);
+                self.line(&format!("store float {}, ptr %v_{}", value, variable));
+            },
+            Kind::Input { variable } => {
+                // if (0 == scanf("%f", &v)) { v = 0; scanf("%*s"); }
+                let format = self.string("%f");
+                let read = self.value();
+                self.line(&format!("{} = call i32 (ptr, ...) @scanf({}, ptr %v_{})", read, format, variable));
+                let failed = self.value();
+                self.line(&format!("{} = icmp eq i32 {}, 0", failed, read));
+                let (skip, done) = (self.block_name(), self.block_name());
+                self.branch(&format!("br i1 {}, label %{}, label %{}", failed, skip, done));
+                self.start_block(&skip);
+                self.line(&format!("store float 0.0, ptr %v_{}", variable));
+                let format = self.string("%*s");
+                let ignored = self.value();
+                self.line(&format!("{} = call i32 (ptr, ...) @scanf({})", ignored, format));
+                self.start_block(&done);
+            },
+            Kind::PrintString(text) => {
+                let format = self.string(&format!("{}\n", text));
+                let ignored = self.value();
+                self.line(&format!("{} = call i32 (ptr, ...) @printf({})", ignored, format));
+            },
+            Kind::PrintValue(value) => {
+                // printf("%.2f\n", (float)(x)), the float promoted to double
+                let value = self.load(value, Type::Float);
+                let promoted = self.value();
+                self.line(&format!("{} = fpext float {} to double", promoted, value));
+                let format = self.string("%.2f\n");
+                let ignored = self.value();
+                self.line(&format!("{} = call i32 (ptr, ...) @printf({}, double {})", ignored, format, promoted));
+            },
+            Kind::Label(label) => self.start_block(&format!("L_{}", label)),
+            Kind::Jump(label) => self.branch(&format!("br label %L_{}", label)),
+            Kind::JumpIfZero { condition, target } => {
+                let kind = self.program.operand_type(condition);
+                let value = self.load(condition, kind);
+                // NaN is not zero, so the unordered `une` is true for it
+                let nonzero = self.value();
+                match kind {
+                    Type::Int => self.line(&format!("{} = icmp ne i32 {}, 0", nonzero, value)),
+                    _ => self.line(&format!("{} = fcmp une {} {}, 0.0", nonzero, type_name(kind), value)),
+                }
+                let next = self.block_name();
+                self.branch(&format!("br i1 {}, label %{}, label %L_{}", nonzero, next, target));
+                self.start_block(&next);
+            },
+        }
+    }
+
+    /// Ordered predicates are false when either operand is NaN, except `!=`.
+    fn predicate(op: Op, kind: Type) -> &'static str {
+        match (op, kind) {
+            (Op::Eq, Type::Int) => "eq",
+            (Op::Ne, Type::Int) => "ne",
+            (Op::Lt, Type::Int) => "slt",
+            (Op::Le, Type::Int) => "sle",
+            (Op::Gt, Type::Int) => "sgt",
+            (Op::Ge, Type::Int) => "sge",
+            (Op::Eq, _) => "oeq",
+            (Op::Ne, _) => "une",
+            (Op::Lt, _) => "olt",
+            (Op::Le, _) => "ole",
+            (Op::Gt, _) => "ogt",
+            (Op::Ge, _) => "oge",
+            _ => unreachable!("Not a comparison operator: {}", op.symbol()),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if !self.terminated {
+            self.branch("ret i32 0");
+        }
+
+        let mut output = String::new();
+        for (index, string) in self.strings.iter().enumerate() {
+            let mut bytes = String::new();
+            for byte in string.bytes() {
+                if byte.is_ascii_graphic() && byte != b'"' && byte != b'\\' || byte == b' ' {
+                    bytes.push(byte as char);
+                } else {
+                    bytes += &format!("\\{:02X}", byte);
+                }
+            }
+            output += &format!(
+                "@.str.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+                index,
+                string.len() + 1,
+                bytes,
+            );
+        }
+        output += "\ndeclare i32 @printf(ptr, ...)\ndeclare i32 @scanf(ptr, ...)\n\n";
+
+        output += "define i32 @main() {\nentry:\n";
+        for variable in self.program.variables() {
+            output += &format!("  %v_{} = alloca float\n", variable);
+            output += &format!("  store float 0.0, ptr %v_{}\n", variable);
+        }
+        output += &self.text;
+        output += "}\n";
+        output
+    }
+}
@@ -1,75 +1,286 @@
+use std::env;
 use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{self, Command};
 
-mod lex;
-mod parse;
-mod emitter;
-mod optimizer;
-use emitter::Emitter;
+use compiler::{bytecode, interp, vm, wat, Options, OptionsBuilder, Output, Target};
 
-fn main() {
-    let source = fs::read_to_string("test7.bas").expect("Could not open file!");
+mod cc;
 
-    let lexer = lex::Lexer::new(source.to_string());
-    println!("Lexing completed");
+const USAGE: &str = "usage: compiler [--emit=c|c-library|cfg-dot|bytecode] [--target=c|x86_64-asm|llvm-ir|wasm|python|js|rust] [-O0|-O1|-O2] [--pass=<name>] [--disable-pass=<name>]
+                [--print-after=<name>|all] [--line-directives] [-o <output>] <source.bas>
+       compiler build|run [-O0|-O1|-O2] [--pass=<name>] [--disable-pass=<name>] [--line-directives] [-o <executable>] <source.bas>
+       compiler interpret <source.bas>
+       compiler vm <program.bbc>
+       compiler wasm <program.wat>";
 
-    let mut parser = parse::Parser::new(lexer);
-    let ast = parser.program();
-    println!("Parsing and AST construction completed");
+struct Args {
+    source: String,
+    output: Option<String>,
+    target: Target,
+    options: Options,
+}
 
-    let optimized_ast = optimizer::optimize(ast);
-    println!("Optimization completed!");
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut source = None;
+    let mut output = None;
+    let mut emit = None;
+    let mut target = Target::C;
+    let mut line_directives = false;
+    let mut builder = Options::builder();
 
-    let mut emitter = Emitter::new("prog.c".to_string(), optimized_ast);
-    println!("Compilation finished!");
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(kind) = arg.strip_prefix("--emit=") {
+            emit = match kind {
+                "c" => None,
+                "c-library" => Some(Target::CLibrary),
+                "cfg-dot" => Some(Target::CfgDot),
+                "bytecode" => Some(Target::Bytecode),
+                _ => return Err(format!("unknown --emit kind: {kind}")),
+            };
+        } else if let Some(kind) = arg.strip_prefix("--target=") {
+            target = match kind {
+                "c" => Target::C,
+                "x86_64-asm" => Target::X86_64Asm,
+                "llvm-ir" => Target::LlvmIr,
+                "wasm" => Target::Wasm,
+                "python" => Target::Python,
+                "js" => Target::JavaScript,
+                "rust" => Target::Rust,
+                _ => return Err(format!("unknown --target: {kind}")),
+            };
+        } else if let Some(digit) = arg.strip_prefix("-O") {
+            builder = match digit {
+                "0" | "1" | "2" => builder.opt_level(digit.parse().unwrap()),
+                _ => return Err(format!("unknown optimization level: {arg}")),
+            };
+        } else if let Some(name) = arg.strip_prefix("--pass=") {
+            builder = builder.enable_pass(name);
+        } else if let Some(name) = arg.strip_prefix("--disable-pass=") {
+            builder = builder.disable_pass(name);
+        } else if let Some(name) = arg.strip_prefix("--print-after=") {
+            builder = builder.print_after(name);
+        } else if arg == "--line-directives" {
+            line_directives = true;
+        } else if arg == "-o" {
+            output = Some(args.next().ok_or("-o needs a file name")?.clone());
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option: {arg}"));
+        } else if source.is_none() {
+            source = Some(arg.clone());
+        } else {
+            return Err(format!("unexpected argument: {arg}"));
+        }
+    }
 
-    emitter.print_tree();
+    let source: String = source.ok_or("no source file given")?;
+    // --emit picks the kind of output, --target the language of a program
+    let target = emit.unwrap_or(target);
+    builder = builder.target(target);
+    if line_directives {
+        builder = builder.line_directives(&source);
+    }
+    if let Some(output) = &output {
+        builder = header_for(builder, output);
+    }
+    Ok(Args { options: builder.build()?, source, output, target })
+}
+
+/// The library's header goes next to the C, named after it.
+fn header_for(builder: OptionsBuilder, output: &str) -> OptionsBuilder {
+    let header = Path::new(output).with_extension("h");
+    builder.header(&header.file_name().unwrap_or_default().to_string_lossy())
 }
 
-fn test1() {
-    let source = "LET foobar = 123";
-    let mut lexer = lex::Lexer::new(source.to_string());
-    while lexer.peek() != '\0' {
-        // println!("{0}", lexer.cur_char);
-        lexer.next_char();
+fn usage_error(message: &str) -> ! {
+    eprintln!("{message}\n{USAGE}");
+    process::exit(2);
+}
+
+/// Reports an error that stops the compiler, such as a file it can't read or write.
+fn fatal(message: &str) -> ! {
+    eprintln!("{message}");
+    process::exit(1);
+}
+
+fn read_source(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|error| fatal(&format!("{path}: {error}")))
+}
+
+fn write_file(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) {
+    let path = path.as_ref();
+    if let Err(error) = fs::write(path, contents) {
+        fatal(&format!("{}: {}", path.display(), error));
     }
 }
 
-fn test2() {
-    let source = "+- */ >>= = !=";
-    let mut lexer = lex::Lexer::new(source.to_string());
-    let mut token = lexer.get_token();
-    while token.kind != lex::TokenType::EOF {
-        // println!("{0}", token.kind);
-        token = lexer.get_token();
+/// Compiles, printing the warnings, or the errors before exiting.
+fn compile(args: &Args) -> Output {
+    let source = read_source(&args.source);
+    match compiler::compile(&source, &args.options) {
+        Ok(output) => {
+            for diagnostic in &output.diagnostics {
+                eprintln!("{}", diagnostic);
+            }
+            output
+        },
+        Err(diagnostics) => {
+            for diagnostic in &diagnostics {
+                eprintln!("{}: {}", args.source, diagnostic);
+            }
+            process::exit(1);
+        },
     }
 }
 
-fn test3() {
-    let source = "+- \"This is a string\" # This is a comment!\n */";
-    let mut lexer = lex::Lexer::new(source.to_string());
-    let mut token = lexer.get_token();
-    while token.kind != lex::TokenType::EOF {
-        // println!("{0} {1}", token.kind, token.text);
-        token = lexer.get_token();
+/// Compiles through C with the system C compiler, returning the executable.
+fn build(args: &[String]) -> String {
+    let args = parse_args(args).unwrap_or_else(|message| usage_error(&message));
+    if args.target != Target::C {
+        usage_error("build and run always compile through C");
+    }
+    let source = Path::new(&args.source);
+    let executable = args.output.clone().unwrap_or_else(|| {
+        // next to the source, without overwriting a source that has no extension
+        let extension = if source.extension().is_some() { "" } else { "out" };
+        source.with_extension(extension).to_string_lossy().into_owned()
+    });
+
+    let output = compile(&args);
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let c_file = env::temp_dir().join(format!("{}-{}.c", stem, process::id()));
+    let c_file = c_file.to_string_lossy().into_owned();
+    write_file(&c_file, &output.code);
+
+    let compiler = cc::CCompiler::from_env();
+    let built = compiler.build(&c_file, &executable, &args.source, &output.source_lines, &output.names);
+    let _ = fs::remove_file(&c_file);
+    if let Err(message) = built {
+        fatal(&message);
     }
+    executable
 }
 
-fn test4() {
-    let source = "+-123 9.8654*/";
-    let mut lexer = lex::Lexer::new(source.to_string());
-    let mut token = lexer.get_token();
-    while token.kind != lex::TokenType::EOF {
-        // println!("{0} {1}", token.kind, token.text);
-        token = lexer.get_token();
+fn build_and_run(args: &[String]) {
+    let executable = build(args);
+    // a bare name would be looked up on PATH
+    let command = if executable.contains('/') { executable } else { format!("./{executable}") };
+    let status = Command::new(&command).status().unwrap_or_else(|error| {
+        eprintln!("could not run {command}: {error}");
+        process::exit(1);
+    });
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+    // as a shell reports a program killed by a signal
+    process::exit(status.code().or(signal.map(|signal| 128 + signal)).unwrap_or(1));
+}
+
+fn interpret(args: &[String]) {
+    let [path] = args else {
+        usage_error("interpret takes exactly one source file");
+    };
+    let ast = compiler::parse(&read_source(path)).unwrap_or_else(|diagnostics| {
+        for diagnostic in &diagnostics {
+            eprintln!("{path}: {diagnostic}");
+        }
+        process::exit(1);
+    });
+
+    let stdin = io::stdin().lock();
+    let stdout = io::BufWriter::new(io::stdout().lock());
+    let mut interpreter = interp::Interpreter::new(stdin, stdout);
+    if let Err(diagnostic) = interpreter.run(&ast) {
+        eprintln!("{}", diagnostic);
+        process::exit(1);
+    }
+}
+
+fn run_bytecode(args: &[String]) {
+    let [path] = args else {
+        usage_error("vm takes exactly one bytecode file");
+    };
+    let bytes = fs::read(path).unwrap_or_else(|error| fatal(&format!("{path}: {error}")));
+    let chunk = bytecode::Chunk::from_bytes(&bytes).unwrap_or_else(|message| {
+        eprintln!("{path}: {message}");
+        process::exit(1);
+    });
+
+    let stdin = io::stdin().lock();
+    let stdout = io::BufWriter::new(io::stdout().lock());
+    let mut machine = vm::Vm::new(stdin, stdout);
+    if let Err(diagnostic) = machine.run(&chunk) {
+        eprintln!("{}", diagnostic);
+        process::exit(1);
+    }
+}
+
+fn run_wasm(args: &[String]) {
+    let [path] = args else {
+        usage_error("wasm takes exactly one WebAssembly text file");
+    };
+    let text = read_source(path);
+    let module = wat::Module::load(&text).unwrap_or_else(|message| {
+        eprintln!("{path}: invalid module: {message}");
+        process::exit(1);
+    });
+
+    let stdin = io::stdin().lock();
+    let stdout = io::BufWriter::new(io::stdout().lock());
+    let mut runner = wat::Runner::new(stdin, stdout);
+    if let Err(message) = runner.run(&module) {
+        eprintln!("{path}: trap: {message}");
+        process::exit(1);
     }
 }
 
-fn test5() {
-    let source = "IF+-123 foo*THEN/";
-    let mut lexer = lex::Lexer::new(source.to_string());
-    let mut token = lexer.get_token();
-    while token.kind != lex::TokenType::EOF {
-        // println!("{0} {1}", token.kind, token.text);
-        token = lexer.get_token();
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("build") => {
+            build(&args[1..]);
+            println!("Compilation finished!");
+            return;
+        },
+        Some("run") => return build_and_run(&args[1..]),
+        Some("interpret") => return interpret(&args[1..]),
+        Some("vm") => return run_bytecode(&args[1..]),
+        Some("wasm") => return run_wasm(&args[1..]),
+        _ => {}
+    }
+    let args = parse_args(&args).unwrap_or_else(|message| usage_error(&message));
+    let output = compile(&args);
+
+    let default = match args.target {
+        Target::C | Target::CLibrary => "prog.c",
+        Target::Bytecode => "prog.bbc",
+        Target::X86_64Asm => "prog.s",
+        Target::LlvmIr => "prog.ll",
+        Target::Wasm => "prog.wat",
+        Target::Python => "prog.py",
+        Target::JavaScript => "prog.js",
+        Target::Rust => "main.rs",
+        Target::CfgDot => {
+            // the graph goes to stdout unless asked for in a file
+            match args.output {
+                Some(path) => write_file(path, &output.code),
+                None => {
+                    let mut stdout = io::stdout().lock();
+                    if let Err(error) = stdout.write_all(&output.code).and_then(|_| stdout.flush()) {
+                        fatal(&format!("stdout: {error}"));
+                    }
+                },
+            }
+            return;
+        },
+    };
+    let path = args.output.unwrap_or_else(|| default.to_string());
+    write_file(&path, &output.code);
+    if let Some(header) = &output.header {
+        write_file(Path::new(&path).with_extension("h"), header);
     }
-}
\ No newline at end of file
+    println!("Compilation finished!");
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+/*
+C identifiers for BASIC names
+
+BASIC identifiers are a letter followed by letters and digits, so they are
+already C identifiers, but some of them mean something else in C: keywords
+like `int` and `return`, and names the generated C itself uses or gets from
+<stdio.h> and <math.h> like `main`, `printf` and `NAN`. Those get an
+underscore appended, `int_`, `main_`.
+
+BASIC names never contain an underscore, so a mangled name can't clash with
+another BASIC name, nor with the temporaries (`t_0`) and labels (`while_0`)
+the compiler makes up. Every name that changes is remembered, so messages
+about the C can be put in terms of the BASIC again.
+*/
+
+const RESERVED: &[&str] = &[
+    // C keywords, up to C23
+    "alignas", "alignof", "auto", "bool", "break", "case", "char", "const", "constexpr", "continue",
+    "default", "do", "double", "else", "enum", "extern", "false", "float", "for", "goto", "if", "inline",
+    "int", "long", "nullptr", "register", "restrict", "return", "short", "signed", "sizeof", "static",
+    "static_assert", "struct", "switch", "thread_local", "true", "typedef", "typeof", "typeof_unqual",
+    "union", "unsigned", "void", "volatile", "while",
+    // keywords of gcc's and clang's default GNU dialect
+    "asm",
+    // what the generated C calls or includes
+    "main", "printf", "scanf", "stdin", "stdout", "stderr", "EOF", "NULL", "BUFSIZ", "FILENAME_MAX",
+    "FOPEN_MAX", "INFINITY", "NAN", "HUGE_VAL", "HUGE_VALF", "HUGE_VALL", "isnan", "isinf", "isfinite",
+    "isnormal", "signbit", "fpclassify", "isgreater", "isgreaterequal", "isless", "islessequal",
+    "islessgreater", "isunordered", "getc", "putc", "getchar", "putchar",
+];
+
+#[derive(Default, Clone)]
+pub struct Mangler {
+    /// BASIC names by the C identifiers given to them.
+    reverse: HashMap<String, String>,
+}
+
+impl Mangler {
+    /// The C identifier for a BASIC variable, label or procedure name.
+    pub fn mangle(&mut self, name: &str) -> String {
+        if !RESERVED.contains(&name) {
+            return name.to_string();
+        }
+        let mangled = format!("{}_", name);
+        self.reverse.insert(mangled.clone(), name.to_string());
+        mangled
+    }
+
+    /// Every changed name, C identifier first.
+    pub fn names(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.reverse.iter().map(|(mangled, name)| (mangled.as_str(), name.as_str()))
+    }
+}
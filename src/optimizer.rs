@@ -1,13 +1,134 @@
+use std::collections::{HashMap, HashSet};
 use std::vec;
 
-use crate::{lex::TokenType, lex::Token, parse::Node};
+use crate::{lex::TokenType, lex::Token, parse::Node, parse::Parser};
+use crate::value::{Fault, Value};
+use crate::cfg::{self, Cfg};
+use crate::diagnostics::Diagnostic;
+use crate::ir::{self, Instruction, Kind, Operand, Program};
+use crate::ssa;
 
-pub fn optimize(mut root: Node) -> Node {
-    // r_collapse_unary(&mut root);
-    r_optimize_terms(&mut root);
-    // root.print_tree(0);
-    r_optimize_expression(&mut root);
-    return root;
+/*
+Pass manager
+
+The AST passes fold and simplify single expressions, then the program is
+lowered to the IR (see `ir.rs`) where the IR passes run, in the order below,
+until none of them changes anything. `-O0` runs no pass, `-O1` every pass that
+keeps the program's exact floating-point results and `-O2` adds
+reassociation, which may not (like C's -ffast-math). Passes can be added to or
+removed from a level by name, and the AST or IR dumped after any of them.
+*/
+
+struct Context {
+    dead_in_source: HashSet<u32>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Copy, Clone)]
+enum Run {
+    Ast(fn(&mut Node)),
+    Ir(fn(&mut Program, &mut Context) -> bool),
+}
+
+struct Pass {
+    name: &'static str,
+    /// Lowest optimization level that enables the pass.
+    level: u8,
+    run: Run,
+}
+
+const PASSES: &[Pass] = &[
+    Pass { name: "unary", level: 1, run: Run::Ast(r_optimize_terms) },
+    Pass { name: "fold-expressions", level: 1, run: Run::Ast(r_optimize_expression) },
+    Pass { name: "reassociate", level: 2, run: Run::Ast(r_simplify) },
+    Pass { name: "fold", level: 1, run: Run::Ir(|program, context| fold_constants(program, &mut context.diagnostics)) },
+    Pass { name: "propagate", level: 1, run: Run::Ir(|program, _| propagate(program)) },
+    Pass { name: "sccp", level: 1, run: Run::Ir(|program, _| ssa::propagate_conditional_constants(program)) },
+    Pass { name: "dce", level: 1, run: Run::Ir(|program, context| {
+        eliminate_dead_code(program, &context.dead_in_source, &mut context.diagnostics)
+    }) },
+    Pass { name: "gvn", level: 1, run: Run::Ir(|program, _| ssa::number_values(program)) },
+    Pass { name: "licm", level: 1, run: Run::Ir(|program, _| ssa::hoist_invariants(program)) },
+];
+
+pub struct PassManager {
+    enabled: Vec<bool>,
+    print_after: Vec<bool>,
+}
+
+impl PassManager {
+    pub fn new(level: u8) -> Self {
+        PassManager {
+            enabled: PASSES.iter().map(|pass| pass.level <= level).collect(),
+            print_after: vec![false; PASSES.len()],
+        }
+    }
+
+    fn find(name: &str) -> Result<usize, String> {
+        PASSES.iter().position(|pass| pass.name == name).ok_or_else(|| {
+            let names: Vec<&str> = PASSES.iter().map(|pass| pass.name).collect();
+            format!("unknown pass: {name} (passes are {})", names.join(", "))
+        })
+    }
+
+    pub fn enable(&mut self, name: &str) -> Result<(), String> {
+        self.enabled[PassManager::find(name)?] = true;
+        Ok(())
+    }
+
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        self.enabled[PassManager::find(name)?] = false;
+        Ok(())
+    }
+
+    /// Dumps the AST or IR to stderr whenever the pass (or any pass, for "all") has run.
+    pub fn print_after(&mut self, name: &str) -> Result<(), String> {
+        if name == "all" {
+            self.print_after = vec![true; PASSES.len()];
+        } else {
+            self.print_after[PassManager::find(name)?] = true;
+        }
+        Ok(())
+    }
+
+    pub fn run(&self, mut root: Node, diagnostics: &mut Vec<Diagnostic>) -> Program {
+        let unoptimized = ir::lower(&root);
+        let mut context = Context {
+            dead_in_source: dead_store_lines(&unoptimized),
+            diagnostics: out_of_range_stores(&unoptimized),
+        };
+
+        for (index, pass) in PASSES.iter().enumerate() {
+            if let (true, Run::Ast(run)) = (self.enabled[index], pass.run) {
+                run(&mut root);
+                if self.print_after[index] {
+                    eprint!("*** AST after {} ***\n{}", pass.name, root.tree(0));
+                }
+            }
+        }
+
+        let mut program = ir::lower(&root);
+        for iteration in 1.. {
+            let mut changed = false;
+            for (index, pass) in PASSES.iter().enumerate() {
+                if let (true, Run::Ir(run)) = (self.enabled[index], pass.run) {
+                    let pass_changed = run(&mut program, &mut context);
+                    if self.print_after[index] {
+                        eprint!("*** IR after {} (iteration {}) ***\n{}", pass.name, iteration, program);
+                    }
+                    changed |= pass_changed;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        // passes that run until nothing changes find the same problem every time
+        let mut seen: HashSet<(u32, String)> = HashSet::new();
+        context.diagnostics.retain(|diagnostic| seen.insert((diagnostic.line, diagnostic.message.clone())));
+        diagnostics.append(&mut context.diagnostics);
+        program
+    }
 }
 
 fn r_optimize_terms(node: &mut Node) {
@@ -23,112 +144,608 @@ fn r_optimize_terms(node: &mut Node) {
             let sign = if child.token.kind == TokenType::PLUS { "" } else { "-" };
             let value = child.children[0].token.text.clone();
             if child.children[0].token.kind == TokenType::IDENT {
-                child.children.clear();
-                child.token = Token{text: (sign.to_owned() + &value), kind: TokenType::IDENT};
+                if sign.is_empty() {
+                    *child = child.children[0].clone();
+                }
             } else {
-                let number: i32 = (sign.to_owned() + &value).parse().unwrap();
+                let mut number = Value::parse(&value).unwrap();
+                if !sign.is_empty() {
+                    number = number.negate().unwrap();
+                }
                 child.children.clear();
-                child.token = Token{text: number.to_string(), kind: TokenType::NUMBER};                
+                child.token = Token{text: number.literal(), kind: TokenType::NUMBER, line: child.token.line};
             }
         }
 
-        let mut skip_next = false;
-        let mut new_vec: Vec<Node> = Vec::new();
-        
-        // if node.children.len() == 1 {
-        //     return;
-        // }
+        let new_vec = fold_chain(&node.children);
+        node.children = new_vec;
+        if node.children.len() == 1 {
+            *node = node.children[0].clone();
+        }
 
-        for (i, child) in &mut node.children.iter().enumerate() {
-            if skip_next {
-                skip_next = false;
-                continue;
-            }
+    } else {
+        for child in &mut node.children {
+            r_optimize_terms(child);
+        }
+    }
+}
 
-            new_vec.push(child.clone());
-            
-            if child.token.kind != TokenType::ASTERISK && child.token.kind != TokenType::SLASH {
+fn r_optimize_expression(node: &mut Node) {
+    if node.token.kind == TokenType::EXPRESSION {
+        if node.children.len() == 1 {
+            return;
+        }
+        let new_vec = fold_chain(&node.children);
+        node.children = new_vec;
+        if node.children.len() == 1 {
+            *node = node.children[0].clone();
+        }
+
+    } else {
+        for child in &mut node.children {
+            r_optimize_expression(child);
+        }
+    }
+}
+
+fn fold_chain(children: &[Node]) -> Vec<Node> {
+    // children alternate operand, operator, operand, ... and evaluate left to right,
+    // so only a leading run of numbers can be folded without reassociating
+    let mut new_vec: Vec<Node> = vec![children[0].clone()];
+
+    for pair in children[1..].chunks(2) {
+        let (op, right) = (&pair[0], &pair[1]);
+        if new_vec.len() == 1 && new_vec[0].token.kind == TokenType::NUMBER && right.token.kind == TokenType::NUMBER {
+            let left_val = Value::parse(&new_vec[0].token.text).unwrap();
+            let right_val = Value::parse(&right.token.text).unwrap();
+
+            if let Some(combo) = Value::binary(op.token.kind, left_val, right_val) {
+                new_vec[0] = leaf(&combo.literal(), TokenType::NUMBER);
                 continue;
             }
-            let left = &new_vec[new_vec.len()-2].clone();
-            let right = &node.children[i+1];
+        }
+        new_vec.push(op.clone());
+        new_vec.push(right.clone());
+    }
+    new_vec
+}
 
-            if left.token.kind != TokenType::NUMBER || right.token.kind != TokenType::NUMBER {
-                continue;
+/*
+Algebraic simplification
+
+Every expression is a sum of products of numbers and variables (the grammar has
+no parentheses), so it is flattened into a list of `Product`s, like terms are
+combined and the result is rebuilt. This reassociates floating point math the
+way `-ffast-math` does: `2 * x * 3` becomes `6 * x`, `1 + x + 2` becomes
+`x + 3`. Terms only cancel when they are constants: `x * 0` and `x - x` are
+NaN when `x` is infinite or NaN, so an expression where a variable's terms
+add up to zero is left as written.
+*/
+
+/// `coefficient op1 v1 op2 v2 ...`, where each op is `*` or `/`.
+#[derive(Clone)]
+struct Product {
+    coefficient: Value,
+    factors: Vec<(TokenType, String)>,
+}
+
+fn r_simplify(node: &mut Node) {
+    match node.token.kind {
+        TokenType::PRINT => {
+            if node.children[0].token.kind != TokenType::STRING {
+                simplify_expression(&mut node.children[0]);
             }
+        },
+        TokenType::LET => simplify_expression(&mut node.children[1]),
+        TokenType::COMPARISON => {
+            for child in &mut node.children {
+                if !Parser::is_comparison(&child.token.text) {
+                    simplify_expression(child);
+                }
+            }
+        },
+        _ => {
+            for child in &mut node.children {
+                r_simplify(child);
+            }
+        }
+    }
+}
 
-            let left_val: i32 = left.token.text.parse().unwrap();
-            let right_val: i32 = right.token.text.parse().unwrap();
-            let mut combo: f32;
-            if child.token.kind == TokenType::ASTERISK {
-                combo = (left_val as f32) * right_val as f32;
-            } else {
-                combo = (left_val as f32) / right_val as f32;
+fn simplify_expression(node: &mut Node) {
+    if let Some(sum) = linearize_sum(node) {
+        if let Some(sum) = combine_like_terms(sum) {
+            *node = rebuild_sum(&sum);
+        }
+    }
+}
+
+fn linearize_sum(node: &Node) -> Option<Vec<Product>> {
+    if node.token.kind != TokenType::EXPRESSION {
+        return Some(vec![linearize_product(node)?]);
+    }
+    let mut sum = vec![linearize_product(&node.children[0])?];
+    for pair in node.children[1..].chunks(2) {
+        let mut product = linearize_product(&pair[1])?;
+        if pair[0].token.kind == TokenType::MINUS {
+            product.coefficient = product.coefficient.negate()?;
+        }
+        sum.push(product);
+    }
+    Some(sum)
+}
+
+fn linearize_product(node: &Node) -> Option<Product> {
+    match node.token.kind {
+        TokenType::NUMBER => Some(Product { coefficient: Value::parse(&node.token.text)?, factors: vec![] }),
+        TokenType::IDENT => Some(Product { coefficient: Value::Int(1), factors: vec![(TokenType::ASTERISK, node.token.text.clone())] }),
+        TokenType::PLUS | TokenType::MINUS if node.children.len() == 1 => {
+            let mut product = linearize_product(&node.children[0])?;
+            if node.token.kind == TokenType::MINUS {
+                product.coefficient = product.coefficient.negate()?;
             }
-            new_vec.pop();
-            new_vec.pop();
+            Some(product)
+        },
+        TokenType::TERM => {
+            let mut product = linearize_product(&node.children[0])?;
+            for pair in node.children[1..].chunks(2) {
+                let (op, operand) = (pair[0].token.kind, linearize_product(&pair[1])?);
+                product.coefficient = if op == TokenType::ASTERISK {
+                    Value::binary(op, product.coefficient, operand.coefficient)?
+                } else {
+                    // past the first variable the chain is evaluated in floating point
+                    let dividend = Value::Double(product.coefficient.as_f64());
+                    Value::binary(op, dividend, operand.coefficient)?
+                };
+                for (_, name) in operand.factors {
+                    product.factors.push((op, name));
+                }
+            }
+            Some(product)
+        },
+        _ => None,
+    }
+}
 
-            new_vec.push(Node { token: Token { text: combo.to_string(), kind: TokenType::NUMBER }, children: vec![] });
-            skip_next = true;
+fn combine_like_terms(sum: Vec<Product>) -> Option<Vec<Product>> {
+    let mut constant: Option<Value> = None;
+    let mut terms: Vec<Product> = Vec::new();
+
+    for product in sum {
+        if product.factors.is_empty() {
+            constant = Some(match constant {
+                Some(value) => Value::binary(TokenType::PLUS, value, product.coefficient)?,
+                None => product.coefficient,
+            });
+        } else if let Some(term) = terms.iter_mut().find(|term| term.factors == product.factors) {
+            term.coefficient = Value::binary(TokenType::PLUS, term.coefficient, product.coefficient)?;
+        } else {
+            terms.push(product);
         }
-        node.children = new_vec;
-        if node.children.len() == 1 {
-            *node = Node{token: node.children[0].token.clone(), children: vec![]};
+    }
+
+    if terms.iter().any(|term| term.coefficient.as_f64() == 0.0) {
+        return None;
+    }
+    match constant {
+        Some(value) if value.as_f64() != 0.0 || terms.is_empty() => {
+            terms.push(Product { coefficient: value, factors: vec![] });
+        },
+        None if terms.is_empty() => {
+            terms.push(Product { coefficient: Value::Int(0), factors: vec![] });
+        },
+        _ => {}
+    }
+    Some(terms)
+}
+
+fn rebuild_sum(sum: &[Product]) -> Node {
+    let mut children: Vec<Node> = Vec::new();
+    for (i, product) in sum.iter().enumerate() {
+        let negative = product.coefficient.as_f64() < 0.0;
+        if i == 0 {
+            children.push(rebuild_product(product));
+            continue;
+        }
+
+        let op = if negative { TokenType::MINUS } else { TokenType::PLUS };
+        let text = if negative { "-" } else { "+" };
+        children.push(leaf(text, op));
+
+        let mut magnitude = product.clone();
+        if negative {
+            magnitude.coefficient = magnitude.coefficient.negate().unwrap();
         }
+        children.push(rebuild_product(&magnitude));
+    }
+
+    if children.len() == 1 {
+        return children.pop().unwrap();
+    }
+    Node { token: Token { text: String::from("expression"), kind: TokenType::EXPRESSION, line: 0 }, children }
+}
+
+fn rebuild_product(product: &Product) -> Node {
+    if product.factors.is_empty() {
+        return leaf(&product.coefficient.literal(), TokenType::NUMBER);
+    }
+
+    let mut children: Vec<Node> = Vec::new();
+    let leading_variable = product.factors[0].0 == TokenType::ASTERISK;
+    let magnitude = product.coefficient.as_f64().abs();
 
+    if leading_variable && magnitude == 1.0 {
+        let first = leaf(&product.factors[0].1, TokenType::IDENT);
+        if product.coefficient.as_f64() < 0.0 {
+            children.push(Node { token: Token { text: String::from("-"), kind: TokenType::MINUS, line: 0 }, children: vec![first] });
+        } else {
+            children.push(first);
+        }
     } else {
-        for child in &mut node.children {
-            r_optimize_terms(child);
+        children.push(leaf(&product.coefficient.literal(), TokenType::NUMBER));
+        children.push(operator(product.factors[0].0));
+        children.push(leaf(&product.factors[0].1, TokenType::IDENT));
+    }
+    for (op, name) in &product.factors[1..] {
+        children.push(operator(*op));
+        children.push(leaf(name, TokenType::IDENT));
+    }
+
+    if children.len() == 1 {
+        return children.pop().unwrap();
+    }
+    Node { token: Token { text: String::from("term"), kind: TokenType::TERM, line: 0 }, children }
+}
+
+fn operator(op: TokenType) -> Node {
+    leaf(if op == TokenType::ASTERISK { "*" } else { "/" }, op)
+}
+
+fn leaf(text: &str, kind: TokenType) -> Node {
+    Node { token: Token { text: text.to_string(), kind, line: 0 }, children: vec![] }
+}
+
+/*
+Constant folding
+
+Temporaries whose operands are all constants are computed at compile time and
+their uses replaced by the result. Division by zero, overflow and NaN results
+(see `Value::checked`) are reported and left for the program to perform.
+Constants stored into a variable that are too large for a float, which C
+turns into infinity, are reported once before any pass runs.
+*/
+
+fn fold_constants(program: &mut Program, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    let mut constants: HashMap<usize, Value> = HashMap::new();
+    let mut changed = false;
+
+    program.instructions.retain_mut(|instruction| {
+        for operand in instruction.operands_mut() {
+            if let Operand::Temp(temp) = operand {
+                if let Some(value) = constants.get(temp) {
+                    *operand = Operand::Constant(*value);
+                    changed = true;
+                }
+            }
+        }
+        let folded = match &instruction.kind {
+            Kind::Binary { op, left: Operand::Constant(left), right: Operand::Constant(right), .. } => op.evaluate(*left, *right),
+            Kind::Negate { operand: Operand::Constant(value), .. } => value.negate().ok_or(Fault::IntegerOverflow),
+            _ => return true,
+        };
+        match (folded, instruction.dest()) {
+            (Ok(value), Some(dest)) => {
+                constants.insert(dest, value);
+                changed = true;
+                false
+            },
+            (Err(fault), _) => {
+                diagnostics.push(Diagnostic::warning(instruction.line, fault.to_string()));
+                true
+            },
+            _ => true,
+        }
+    });
+    changed
+}
+
+fn out_of_range_stores(program: &Program) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for instruction in &program.instructions {
+        if let Kind::Store { value: Operand::Constant(value), .. } = &instruction.kind {
+            if !value.as_float().is_finite() {
+                diagnostics.push(Diagnostic::warning(instruction.line, "value is out of range of float and becomes infinity".to_string()));
+            }
         }
     }
+    diagnostics
 }
 
-fn r_optimize_expression(node: &mut Node) {
-    if node.token.kind == TokenType::EXPRESSION {
-        let mut skip_next = false;
-        let mut new_vec: Vec<Node> = Vec::new();
-        if node.children.len() == 1 {
-            return;
+/*
+Constant and copy propagation
+
+A forward dataflow analysis over the control-flow graph. The state maps each
+variable to what is known about it at a program point: a constant, or that it
+holds a copy of another variable. States are joined where control flow merges,
+so loops, IF bodies and GOTO targets only keep facts that hold on every path
+into them. INPUT forgets everything about its variable. `None` marks a block
+not reached yet.
+*/
+
+#[derive(Clone, PartialEq)]
+enum Fact {
+    Constant(Value),
+    Copy(String),
+}
+
+type State = Option<HashMap<String, Fact>>;
+
+fn propagate(program: &mut Program) -> bool {
+    let graph = cfg::build(program);
+    let mut states: Vec<State> = vec![None; graph.blocks.len()];
+    states[graph.entry] = Some(HashMap::new());
+
+    let mut worklist = vec![graph.entry];
+    while let Some(index) = worklist.pop() {
+        let block = &graph.blocks[index];
+        let mut known = states[index].clone().unwrap();
+        for instruction in &program.instructions[block.start..block.end] {
+            transfer_facts(&instruction.kind, &mut known);
         }
-        for (i, child) in &mut node.children.iter().enumerate() {
-            if skip_next {
-                skip_next = false;
-                continue;
+        for edge in &block.successors {
+            let joined = join(states[edge.target].clone(), Some(known.clone()));
+            if joined != states[edge.target] {
+                states[edge.target] = joined;
+                worklist.push(edge.target);
             }
+        }
+    }
 
-            new_vec.push(child.clone());            
-            if child.token.kind != TokenType::PLUS && child.token.kind != TokenType::MINUS {
-                continue;
+    let mut changed = false;
+    for (index, block) in graph.blocks.iter().enumerate() {
+        let Some(mut known) = states[index].clone() else {
+            continue;
+        };
+        for instruction in &mut program.instructions[block.start..block.end] {
+            for operand in instruction.operands_mut() {
+                if let Operand::Variable(name) = operand {
+                    match known.get(name) {
+                        Some(Fact::Constant(value)) => *operand = Operand::Constant(*value),
+                        Some(Fact::Copy(source)) => *operand = Operand::Variable(source.clone()),
+                        None => continue,
+                    }
+                    changed = true;
+                }
+            }
+            transfer_facts(&instruction.kind, &mut known);
+        }
+    }
+    changed
+}
+
+fn transfer_facts(kind: &Kind, known: &mut HashMap<String, Fact>) {
+    match kind {
+        Kind::Store { variable, value } => {
+            let fact = match value {
+                Operand::Constant(value) => Some(Fact::Constant(value.as_float())),
+                Operand::Variable(source) if source == variable => return,
+                Operand::Variable(source) => Some(known.get(source).cloned().unwrap_or(Fact::Copy(source.clone()))),
+                Operand::Temp(_) => None,
+            };
+            kill(known, variable);
+            if let Some(fact) = fact {
+                known.insert(variable.clone(), fact);
             }
-            let left = &new_vec[new_vec.len()-2].clone();
-            let right = &node.children[i+1];
+        },
+        Kind::Input { variable } => kill(known, variable),
+        _ => {}
+    }
+}
 
-            if left.token.kind != TokenType::NUMBER || right.token.kind != TokenType::NUMBER {
+fn join(left: State, right: State) -> State {
+    match (left, right) {
+        (None, state) | (state, None) => state,
+        (Some(left), Some(right)) => Some(
+            left.into_iter()
+                .filter(|(name, fact)| right.get(name) == Some(fact))
+                .collect()
+        ),
+    }
+}
+
+fn kill(known: &mut HashMap<String, Fact>, name: &str) {
+    known.remove(name);
+    known.retain(|_, fact| *fact != Fact::Copy(name.to_string()));
+}
+
+/*
+Dead code elimination
+
+Conditional jumps on a constant are resolved first. Instructions in blocks the
+entry cannot reach are then removed, as are stores whose value is not live
+afterwards and temporaries nobody reads. Finally jumps to the very next
+instruction and labels nothing jumps to are dropped. Removed code is reported,
+since unreachable BASIC is usually a mistake. INPUT is never removed and does
+not kill liveness, as it leaves its variable untouched at the end of input.
+Dead stores are only reported when they were already dead in the source, and
+not merely because propagation replaced every read.
+*/
+
+fn eliminate_dead_code(program: &mut Program, dead_in_source: &HashSet<u32>, diagnostics: &mut Vec<Diagnostic>) -> bool {
+    let mut changed = false;
+
+    // branches on constants
+    let mut never_taken: HashSet<usize> = HashSet::new();
+    let mut index = 0;
+    while index < program.instructions.len() {
+        if let Kind::JumpIfZero { condition: Operand::Constant(value), target } = &program.instructions[index].kind {
+            changed = true;
+            if value.as_f64() != 0.0 {
+                program.instructions.remove(index);
                 continue;
             }
+            program.instructions[index].kind = Kind::Jump(target.clone());
+            never_taken.insert(index);
+        }
+        index += 1;
+    }
 
-            let left_val: i32 = left.token.text.parse().unwrap();
-            let right_val: i32 = right.token.text.parse().unwrap();
-            let mut combo: f32 = 1.0;
-            if child.token.kind == TokenType::PLUS {
-                combo = (left_val as f32) + right_val as f32;
-            } else {
-                combo = (left_val as f32) - right_val as f32;
+    let graph = cfg::build(program);
+    let reachable = graph.reachable(&|_, _| true);
+    let live_out = liveness(&graph, program);
+    let mut keep = vec![true; program.instructions.len()];
+    let mut unreachable = vec![false; program.instructions.len()];
+
+    for (index, block) in graph.blocks.iter().enumerate() {
+        if !reachable[index] {
+            keep[block.start..block.end].fill(false);
+            unreachable[block.start..block.end].fill(true);
+            continue;
+        }
+        let mut live = live_out[index].clone();
+        for position in (block.start..block.end).rev() {
+            let instruction = &program.instructions[position];
+            if let Kind::Store { variable, .. } = &instruction.kind {
+                if !live.contains(variable) {
+                    keep[position] = false;
+                    if dead_in_source.contains(&instruction.line) {
+                        diagnostics.push(Diagnostic::warning(instruction.line, format!("value assigned to {} is never read", variable)));
+                    }
+                    continue;
+                }
             }
-            new_vec.pop();
-            new_vec.pop();
+            transfer_liveness(instruction, &mut live);
+        }
+    }
 
-            new_vec.push(Node { token: Token { text: combo.to_string(), kind: TokenType::NUMBER }, children: vec![] });
-            skip_next = true;
+    // report each run of unreachable code once
+    let mut position = 0;
+    while position < unreachable.len() {
+        if !unreachable[position] {
+            position += 1;
+            continue;
         }
-        node.children = new_vec;
-        if node.children.len() == 1 {
-            *node = Node{token: node.children[0].token.clone(), children: vec![]};
+        let start = position;
+        while position < unreachable.len() && unreachable[position] {
+            position += 1;
+        }
+        let run = &program.instructions[start..position];
+        let Some(first) = run.iter().find(|instruction| !matches!(instruction.kind, Kind::Label(_) | Kind::Jump(_))) else {
+            continue;
+        };
+        if start > 0 && never_taken.contains(&(start - 1)) {
+            let line = program.instructions[start - 1].line;
+            diagnostics.push(Diagnostic::warning(line, String::from("condition is always false, body removed")));
+        } else {
+            diagnostics.push(Diagnostic::warning(first.line, String::from("unreachable code removed")));
         }
+    }
 
-    } else {
-        for child in &mut node.children {
-            r_optimize_expression(child);
+    changed |= keep.iter().any(|kept| !kept);
+    let mut index = 0;
+    program.instructions.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+
+    changed |= remove_unused_temps(program);
+    changed |= remove_redundant_jumps(program);
+    changed
+}
+
+fn liveness(graph: &Cfg, program: &Program) -> Vec<HashSet<String>> {
+    let mut live_in: Vec<HashSet<String>> = vec![HashSet::new(); graph.blocks.len()];
+    let mut live_out: Vec<HashSet<String>> = vec![HashSet::new(); graph.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..graph.blocks.len()).rev() {
+            let block = &graph.blocks[index];
+            let mut live: HashSet<String> = HashSet::new();
+            for edge in &block.successors {
+                live.extend(live_in[edge.target].iter().cloned());
+            }
+            live_out[index] = live.clone();
+            for instruction in program.instructions[block.start..block.end].iter().rev() {
+                transfer_liveness(instruction, &mut live);
+            }
+            if live != live_in[index] {
+                live_in[index] = live;
+                changed = true;
+            }
+        }
+    }
+    live_out
+}
+
+/// Steps liveness backwards over a single instruction.
+fn transfer_liveness(instruction: &Instruction, live: &mut HashSet<String>) {
+    if let Kind::Store { variable, .. } = &instruction.kind {
+        live.remove(variable);
+    }
+    for operand in instruction.operands() {
+        if let Operand::Variable(name) = operand {
+            live.insert(name.clone());
+        }
+    }
+}
+
+fn dead_store_lines(program: &Program) -> HashSet<u32> {
+    let graph = cfg::build(program);
+    let live_out = liveness(&graph, program);
+    let mut lines = HashSet::new();
+    for (index, block) in graph.blocks.iter().enumerate() {
+        let mut live = live_out[index].clone();
+        for instruction in program.instructions[block.start..block.end].iter().rev() {
+            if let Kind::Store { variable, .. } = &instruction.kind {
+                if !live.contains(variable) {
+                    lines.insert(instruction.line);
+                }
+            }
+            transfer_liveness(instruction, &mut live);
+        }
+    }
+    lines
+}
+
+fn remove_unused_temps(program: &mut Program) -> bool {
+    let mut used: HashSet<usize> = HashSet::new();
+    for instruction in &program.instructions {
+        for operand in instruction.operands() {
+            if let Operand::Temp(temp) = operand {
+                used.insert(*temp);
+            }
         }
     }
-}
\ No newline at end of file
+    let before = program.instructions.len();
+    program.instructions.retain(|instruction| instruction.dest().is_none_or(|dest| used.contains(&dest)));
+    program.instructions.len() != before
+}
+
+fn remove_redundant_jumps(program: &mut Program) -> bool {
+    let before = program.instructions.len();
+    let mut index = 0;
+    while index + 1 < program.instructions.len() {
+        let redundant = match (&program.instructions[index].kind, &program.instructions[index + 1].kind) {
+            (Kind::Jump(target), Kind::Label(label)) => target == label,
+            _ => false,
+        };
+        if redundant {
+            program.instructions.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    let targets: HashSet<String> = program.instructions.iter()
+        .filter_map(|instruction| match &instruction.kind {
+            Kind::Jump(target) | Kind::JumpIfZero { target, .. } => Some(target.clone()),
+            _ => None,
+        })
+        .collect();
+    program.instructions.retain(|instruction| match &instruction.kind {
+        Kind::Label(label) => targets.contains(label),
+        _ => true,
+    });
+    program.instructions.len() != before
+}
@@ -1,3 +1,4 @@
+use crate::diagnostics::Diagnostic;
 use crate::lex::Lexer;
 use crate::lex::Token;
 use crate::lex::TokenType;
@@ -14,21 +15,27 @@ pub struct Node {
 
 impl Node {
     pub fn new() -> Self {
-        let node = Node {
-            token: Token{text: "".to_string(), kind: TokenType::BAD},
+        Node {
+            token: Token{text: "".to_string(), kind: TokenType::BAD, line: 0},
             children: vec![]
-        };
-        node
+        }
     }
 
-    pub fn print_tree(&self, level: usize) {
-        // Print the current node's token text with indentation
-        println!("{}{}", "  ".repeat(level), self.token.text);
+    pub fn tree(&self, level: usize) -> String {
+        // The current node's token text with indentation
+        let mut tree = format!("{}{}\n", "  ".repeat(level), self.token.text);
 
-        // Recursively print all children nodes
+        // Recursively all children nodes
         for child in &self.children {
-            child.print_tree(level + 1);
+            tree += &child.tree(level + 1);
         }
+        tree
+    }
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node::new()
     }
 }
 
@@ -38,7 +45,7 @@ pub struct Parser {
     peek_token: Token,
     symbols: HashSet<String>,
     labels_declared: HashSet<String>,
-    labels_gotoed: HashSet<String>,
+    labels_gotoed: Vec<(String, u32)>,
     ast: Node
 }
 
@@ -58,47 +65,50 @@ expression ::= term {( "-" | "+" ) term}
 term ::= unary {( "/" | "*" ) unary}
 unary ::= ["+" | "-"] primary
 primary ::= number | ident
+number ::= digits ["." digits] [exponent] | "." digits [exponent]
+    | "&H" hexdigits | "&O" octdigits | "&B" bindigits
+exponent ::= ("e" | "E") ["+" | "-"] digits
 nl ::= '\n'+
 */
 
 impl Parser {
-    pub fn new(input_lexer: Lexer) -> Self {
+    pub fn new(input_lexer: Lexer) -> Result<Self, Diagnostic> {
         let mut parser = Parser {
             lexer: input_lexer,
-            cur_token: Token{text: "".to_string(), kind: TokenType::BAD},
-            peek_token: Token{text: "".to_string(), kind: TokenType::BAD},
+            cur_token: Token{text: "".to_string(), kind: TokenType::BAD, line: 0},
+            peek_token: Token{text: "".to_string(), kind: TokenType::BAD, line: 0},
             symbols: HashSet::new(),
             labels_declared: HashSet::new(),
-            labels_gotoed: HashSet::new(),
+            labels_gotoed: vec![],
             ast: Node::new()
         };
-        parser.next_token();
-        parser.next_token();
-        parser
+        parser.next_token()?;
+        parser.next_token()?;
+        Ok(parser)
     }
 
-    pub fn program(&mut self) -> Node {
+    pub fn program(&mut self) -> Result<Node, Diagnostic> {
         // println!("PROGRAM");
 
         while self.check_token(TokenType::NEWLINE) {
-            self.next_token();
+            self.next_token()?;
         }
 
         while !self.check_token(TokenType::EOF) {
-            let sub_node = self.statement();
+            let sub_node = self.statement()?;
             self.ast.children.push(sub_node);
         }
 
-        for label in &self.labels_gotoed {
+        for (label, line) in &self.labels_gotoed {
             if !self.labels_declared.contains(label) {
-                unreachable!("Attempting to GOTO undeclared label: {label}");
+                return Err(Diagnostic::error(*line, format!("Attempting to GOTO undeclared label: {label}")));
             }
         }
 
-        self.ast.clone()
+        Ok(self.ast.clone())
     }
 
-    fn statement(&mut self) -> Node {
+    fn statement(&mut self) -> Result<Node, Diagnostic> {
         // "PRINT" (expression | string)
 
         let mut node = Node {
@@ -109,238 +119,237 @@ impl Parser {
         if self.check_token(TokenType::PRINT) {
             // println!("STATEMENT-PRINT");
 
-            self.next_token();
+            self.next_token()?;
             if self.check_token(TokenType::STRING) {
                 node.children.push(Node { token: self.cur_token.clone(), children: vec![] });
-                self.next_token();
+                self.next_token()?;
             } else {
                 // expect expression
-                node.children.push(self.expression());
+                node.children.push(self.expression()?);
             }
         } else if self.check_token(TokenType::IF) {
             // | "IF" comparison "THEN" nl {statement} "ENDIF" nl
             // println!("STATEMENT-IF");
 
-            self.next_token();
+            self.next_token()?;
             // node.children.push(self.comparison());
-            node.children.push(self.comparison());
+            node.children.push(self.comparison()?);
 
 
-            self.match_token(TokenType::THEN);
-            self.nl();
+            self.match_token(TokenType::THEN)?;
+            self.nl()?;
 
             while !self.check_token(TokenType::ENDIF) {
                 // node.children.push(self.statement());
-                node.children.push(self.statement());
+                node.children.push(self.statement()?);
             }
-            self.match_token(TokenType::ENDIF);
+            self.match_token(TokenType::ENDIF)?;
 
         } else if self.check_token(TokenType::WHILE) {
             // | "WHILE" comparison "REPEAT" nl {statement} "ENDWHILE" nl
             // println!("STATEMENT-WHILE");
 
-            self.next_token();
-            node.children.push(self.comparison());
+            self.next_token()?;
+            node.children.push(self.comparison()?);
 
-            self.match_token(TokenType::REPEAT);
-            self.nl();
+            self.match_token(TokenType::REPEAT)?;
+            self.nl()?;
 
             while !self.check_token(TokenType::ENDWHILE) {
-                node.children.push(self.statement());
+                node.children.push(self.statement()?);
             }
-            self.match_token(TokenType::ENDWHILE);
+            self.match_token(TokenType::ENDWHILE)?;
 
         } else if self.check_token(TokenType::LABEL) {
             // | "LABEL" ident nl
             // println!("STATEMENT-LABEL");
 
-            self.next_token();
+            self.next_token()?;
 
             if self.labels_declared.contains(&self.cur_token.text) {
-                unreachable!("Label {0} is already declared!", self.cur_token.text);
+                return Err(self.error(format!("Label {0} is already declared!", self.cur_token.text)));
             }
             self.labels_declared.insert(self.cur_token.text.clone());
 
-            self.match_token(TokenType::IDENT);
+            node.children.push(Node { token: self.cur_token.clone(), children: vec![] });
+            self.match_token(TokenType::IDENT)?;
             
         } else if self.check_token(TokenType::GOTO) {
             // | "GOTO" ident nl
             // println!("STATEMENT-GOTO");
 
-            self.next_token();
-            self.labels_gotoed.insert(self.cur_token.text.clone());
-            self.match_token(TokenType::IDENT);
+            self.next_token()?;
+            self.labels_gotoed.push((self.cur_token.text.clone(), self.cur_token.line));
+            node.children.push(Node { token: self.cur_token.clone(), children: vec![] });
+            self.match_token(TokenType::IDENT)?;
             
         } else if self.check_token(TokenType::LET) {
             // | "LET" ident "=" expression nl
             // println!("STATEMENT-LET");
 
-            self.next_token();
+            self.next_token()?;
             
             if !self.symbols.contains(&self.cur_token.text) {
                 self.symbols.insert(self.cur_token.text.clone());
             }
             
             node.children.push(Node { token: self.cur_token.clone(), children: vec![] });
-            self.match_token(TokenType::IDENT);
-            self.match_token(TokenType::EQ);
-            node.children.push(self.expression());
+            self.match_token(TokenType::IDENT)?;
+            self.match_token(TokenType::EQ)?;
+            node.children.push(self.expression()?);
             
         } else if self.check_token(TokenType::INPUT) {
             // | "INPUT" ident nl
             // println!("STATEMENT-INPUT");
 
-            self.next_token();
+            self.next_token()?;
 
             if !self.symbols.contains(&self.cur_token.text) {
                 self.symbols.insert(self.cur_token.text.clone());
             }
             
             node.children.push(Node { token: self.cur_token.clone(), children: vec![] });
-            self.match_token(TokenType::IDENT);
+            self.match_token(TokenType::IDENT)?;
             
         } else {
             // println!("Not a valid statement! Got {0} of type {1}", self.cur_token.text, self.cur_token.text);
         }
 
         // newline
-        self.nl();
-        node
+        self.nl()?;
+        Ok(node)
     }
 
-    fn comparison(&mut self) -> Node {
+    fn comparison(&mut self) -> Result<Node, Diagnostic> {
         // comparison ::= expression (("==" | "!=" | ">" | ">=" | "<" | "<=") expression)+
         // println!("COMPARISON");
 
         let mut node: Node = Node {
-            token: Token { text: String::from("comparison"), kind: TokenType::COMPARISON },
+            token: Token { text: String::from("comparison"), kind: TokenType::COMPARISON, line: self.cur_token.line },
             children: vec![]
         };
 
-        node.children.push(self.expression());
+        node.children.push(self.expression()?);
 
 
         if !Parser::is_comparison(&self.cur_token.text) {
-            unreachable!("Expected comparison token, got {0} instead", self.cur_token.text);
+            return Err(self.error(format!("Expected comparison token, got {0} instead", self.cur_token.text)));
         }
         node.children.push(Node{token: self.cur_token.clone(), children: vec![]});
 
-        self.next_token();
-        node.children.push(self.expression());
+        self.next_token()?;
+        node.children.push(self.expression()?);
 
         while Parser::is_comparison(&self.cur_token.text) {
             node.children.push(Node{token: self.cur_token.clone(), children: vec![]});
-            self.next_token();
-            node.children.push(self.expression());
+            self.next_token()?;
+            node.children.push(self.expression()?);
         }
 
-        node
+        Ok(node)
     }
 
     pub fn is_comparison(op: &str) -> bool {
-        match op {
-            "==" => true,
-            "!=" => true,
-            ">" => true,
-            ">=" => true,
-            "<" => true,
-            "<=" => true,
-            _ => false
-        }
+        matches!(op, "==" | "!=" | ">" | ">=" | "<" | "<=")
     }
 
-    fn expression(&mut self) -> Node {
+    fn expression(&mut self) -> Result<Node, Diagnostic> {
         // expression ::= term {( "-" | "+" ) term}
         // println!("EXPRESSION");
 
         let mut node = Node {
-            token: Token { text: String::from("expression"), kind: TokenType::EXPRESSION },
+            token: Token { text: String::from("expression"), kind: TokenType::EXPRESSION, line: self.cur_token.line },
             children: vec![]
         };
 
-        node.children.push(self.term());
+        node.children.push(self.term()?);
         while self.check_token(TokenType::PLUS) || self.check_token(TokenType::MINUS) {
             node.children.push(Node{token: self.cur_token.clone(), children: vec![]});
-            self.next_token();
-            node.children.push(self.term());
+            self.next_token()?;
+            node.children.push(self.term()?);
         }
 
-        node
+        Ok(node)
     }
 
-    fn term(&mut self) -> Node {
+    fn term(&mut self) -> Result<Node, Diagnostic> {
         // term ::= unary {( "/" | "*" ) unary}
         // println!("TERM");
 
         let mut node = Node {
-            token: Token { text: String::from("term"), kind: TokenType::TERM },
+            token: Token { text: String::from("term"), kind: TokenType::TERM, line: self.cur_token.line },
             children: vec![]
         };
 
-        node.children.push(self.unary());
+        node.children.push(self.unary()?);
         while self.check_token(TokenType::SLASH) || self.check_token(TokenType::ASTERISK) {
             node.children.push(Node{token: self.cur_token.clone(), children: vec![]});
-            self.next_token();
-            node.children.push(self.unary());
+            self.next_token()?;
+            node.children.push(self.unary()?);
         }
 
-        node
+        Ok(node)
     }
 
-    fn unary(&mut self) -> Node {
+    fn unary(&mut self) -> Result<Node, Diagnostic> {
         // unary ::= ["+" | "-"] primary
         // println!("UNARY");
 
-        let mut old_token = Token { text: "".to_string(), kind: TokenType::PLUS };
+        let mut old_token = Token { text: "".to_string(), kind: TokenType::PLUS, line: self.cur_token.line };
 
         // optional to handle cases like +2, -3, -3 * +2 etc.
         if self.check_token(TokenType::PLUS) || self.check_token(TokenType::MINUS) {
             old_token = self.cur_token.clone();
-            self.next_token();
+            self.next_token()?;
         }
 
-        let child = self.primary();
-        Node {
+        let child = self.primary()?;
+        Ok(Node {
             token: old_token,
             children: vec![child]
-        }
+        })
     }
 
-    fn primary(&mut self) -> Node {
+    fn primary(&mut self) -> Result<Node, Diagnostic> {
         // println!("PRIMARY ({0})", self.cur_token.text);
         // primary ::= number | ident
 
         let old_token = self.cur_token.clone();
         if self.check_token(TokenType::NUMBER) {
-            self.next_token();
+            self.next_token()?;
         } else if self.check_token(TokenType::IDENT) {
             if !self.symbols.contains(&self.cur_token.text) {
-                unreachable!("Attempting to reference variable before assignment {0}", self.cur_token.text);
+                return Err(self.error(format!("Attempting to reference variable before assignment {0}", self.cur_token.text)));
             }
-            self.next_token();
+            self.next_token()?;
         } else {
-            unreachable!("Unexpected Primary token of {0}", self.cur_token.text);
+            return Err(self.error(format!("Unexpected Primary token of {0}", self.cur_token.text)));
         }
 
-        Node {
+        Ok(Node {
             token: old_token,
             children: vec![]
-        }
+        })
     }
 
-    fn nl(&mut self) {
+    fn nl(&mut self) -> Result<(), Diagnostic> {
         // println!("NEWLINE");
-        self.match_token(TokenType::NEWLINE);
+        self.match_token(TokenType::NEWLINE)?;
         while self.check_token(TokenType::NEWLINE) {
-            self.next_token();
+            self.next_token()?;
         }
+        Ok(())
     }
 
-    fn match_token(&mut self, token_type: TokenType) {
+    fn match_token(&mut self, token_type: TokenType) -> Result<(), Diagnostic> {
         if !self.check_token(token_type) {
-            unreachable!("Expected {0}, got {1}", token_type, self.cur_token.kind)
+            return Err(self.error(format!("Expected {0}, got {1}", token_type, self.cur_token.kind)));
         }
-        self.next_token();
+        self.next_token()
+    }
+
+    fn error(&self, message: String) -> Diagnostic {
+        Diagnostic::error(self.cur_token.line, message)
     }
 
     fn check_token(&mut self, token_type: TokenType) -> bool {
@@ -351,8 +360,9 @@ impl Parser {
     //     self.peek_token.kind == token_type
     // }
 
-    fn next_token(&mut self) {
+    fn next_token(&mut self) -> Result<(), Diagnostic> {
         self.cur_token = self.peek_token.clone();
-        self.peek_token = self.lexer.get_token();
+        self.peek_token = self.lexer.get_token()?;
+        Ok(())
     }
 }
\ No newline at end of file
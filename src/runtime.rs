@@ -0,0 +1,202 @@
+use std::io::{BufRead, Write};
+
+/*
+Console input and output with the behaviour of the generated C, shared by the
+interpreter and the bytecode VM: PRINT of a number is
+`printf("%.2f\n", (float)x)` and INPUT is
+`if (0 == scanf("%f", &x)) { x = 0; scanf("%*s"); }`, which leaves the
+variable alone at the end of input.
+*/
+
+pub struct Console<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Console { input, output }
+    }
+
+    pub fn print_string(&mut self, text: &str) {
+        writeln!(self.output, "{}", text).expect("Unable to write output!");
+    }
+
+    pub fn print_value(&mut self, value: f32) {
+        writeln!(self.output, "{}", format_float(value)).expect("Unable to write output!");
+    }
+
+    pub fn flush(&mut self) {
+        self.output.flush().expect("Unable to write output!");
+    }
+
+    /// Reads a number for INPUT, or None at the end of input. Output is
+    /// flushed first so prompts show up.
+    pub fn input(&mut self) -> Option<f32> {
+        self.flush();
+        self.read_float()
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.input.fill_buf().expect("Unable to read input!").first().copied()
+    }
+
+    /// Moves bytes from the input to `text` while `allowed` accepts them, returning how many.
+    fn take_while(&mut self, text: &mut String, allowed: fn(u8) -> bool) -> usize {
+        let mut count = 0;
+        while let Some(byte) = self.peek().filter(|byte| allowed(*byte)) {
+            text.push(byte as char);
+            self.input.consume(1);
+            count += 1;
+        }
+        count
+    }
+
+    /// Moves one byte from the input to `text` if `allowed` accepts it.
+    fn take_one(&mut self, text: &mut String, allowed: fn(u8) -> bool) -> bool {
+        match self.peek().filter(|byte| allowed(*byte)) {
+            Some(byte) => {
+                text.push(byte as char);
+                self.input.consume(1);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Reads a number like `scanf("%f")` followed, on a matching failure, by `scanf("%*s")`.
+    fn read_float(&mut self) -> Option<f32> {
+        let mut skipped = String::new();
+        self.take_while(&mut skipped, |byte| byte.is_ascii_whitespace());
+        self.peek()?;
+
+        let mut text = String::new();
+        self.take_one(&mut text, |byte| byte == b'+' || byte == b'-');
+        let digits = self.take_while(&mut text, |byte| byte.is_ascii_digit());
+        let zero = text.trim_start_matches(['+', '-']) == "0";
+        let value = match zero && self.take_one(&mut text, |byte| byte == b'x' || byte == b'X') {
+            true => self.read_hex(text.starts_with('-')),
+            false => self.read_decimal(text, digits),
+        };
+        if value.is_none() {
+            // scanf("%*s") skips the next word
+            let mut word = String::new();
+            self.take_while(&mut word, |byte| byte.is_ascii_whitespace());
+            self.take_while(&mut word, |byte| !byte.is_ascii_whitespace());
+        }
+        Some(value.unwrap_or(0.0))
+    }
+
+    /// Reads the rest of a decimal number, infinity or NaN.
+    fn read_decimal(&mut self, mut text: String, mut digits: usize) -> Option<f32> {
+        if self.take_one(&mut text, |byte| byte == b'.') {
+            digits += self.take_while(&mut text, |byte| byte.is_ascii_digit());
+        }
+        if digits > 0 && self.take_one(&mut text, |byte| byte == b'e' || byte == b'E') {
+            self.take_one(&mut text, |byte| byte == b'+' || byte == b'-');
+            if self.take_while(&mut text, |byte| byte.is_ascii_digit()) == 0 {
+                text.push('0');
+            }
+        }
+        if digits == 0 {
+            // infinity and NaN are spelled out
+            let start = text.len();
+            while let Some(byte) = self.peek() {
+                let word = format!("{}{}", &text[start..], byte as char).to_ascii_lowercase();
+                if !"infinity".starts_with(&word) && !"nan".starts_with(&word) {
+                    break;
+                }
+                text.push(byte as char);
+                self.input.consume(1);
+            }
+        }
+
+        let special = text.trim_start_matches(['+', '-']).to_ascii_lowercase();
+        match special.as_str() {
+            "inf" | "infinity" | "nan" => text.parse::<f32>().ok(),
+            _ if digits > 0 => text.parse::<f32>().ok(),
+            _ => None,
+        }
+    }
+
+    /// Reads the rest of a hexadecimal number after its `0x`, which glibc's
+    /// scanf accepts as well.
+    fn read_hex(&mut self, negative: bool) -> Option<f32> {
+        let mut mantissa = String::new();
+        let digits = self.take_while(&mut mantissa, |byte| byte.is_ascii_hexdigit());
+        let point = self.take_one(&mut mantissa, |byte| byte == b'.');
+        let digits = digits + self.take_while(&mut mantissa, |byte| byte.is_ascii_hexdigit());
+        if digits == 0 && !point {
+            return None;
+        }
+        let mut exponent = String::new();
+        if digits > 0 && self.take_one(&mut exponent, |byte| byte == b'p' || byte == b'P') {
+            exponent.clear();
+            self.take_one(&mut exponent, |byte| byte == b'+' || byte == b'-');
+            self.take_while(&mut exponent, |byte| byte.is_ascii_digit());
+        }
+        let value = hex_float(&mantissa, &exponent);
+        Some(if negative { -value } else { value })
+    }
+}
+
+/// The float nearest to the hexadecimal `mantissa` times two to the power
+/// `exponent`, rounded to even like strtof.
+fn hex_float(mantissa: &str, exponent: &str) -> f32 {
+    // far beyond the exponents that give 0 or infinity
+    let magnitude = exponent.trim_start_matches(['+', '-']).bytes().fold(0, |magnitude: i64, digit| {
+        (magnitude * 10 + i64::from(digit - b'0')).min(1 << 20)
+    });
+    let mut shift = if exponent.starts_with('-') { -magnitude } else { magnitude };
+    let mut bits: u64 = 0;
+    let mut sticky = false;
+    let mut fraction = false;
+    for digit in mantissa.chars() {
+        match digit.to_digit(16) {
+            None => fraction = true,
+            Some(digit) if bits >> 60 == 0 => {
+                bits = bits << 4 | u64::from(digit);
+                shift -= if fraction { 4 } else { 0 };
+            },
+            // digits past 60 bits only matter when rounding a tie
+            Some(digit) => {
+                sticky |= digit != 0;
+                shift += if fraction { 0 } else { 4 };
+            },
+        }
+    }
+    if bits == 0 {
+        return 0.0;
+    }
+    let bits = u128::from(bits | u64::from(sticky));
+    let width = 128 - i64::from(bits.leading_zeros());
+    let top = width - 1 + shift;
+    if top > 127 {
+        return f32::INFINITY;
+    }
+    if top < -150 {
+        return 0.0;
+    }
+    // 24 significant bits, fewer for a subnormal
+    let drop = width - (top + 150).min(24);
+    let (bits, shift) = if drop > 0 {
+        let (kept, rest, half) = (bits >> drop, bits & ((1 << drop) - 1), 1 << (drop - 1));
+        (kept + u128::from(rest > half || rest == half && kept & 1 == 1), shift + drop)
+    } else {
+        (bits, shift)
+    };
+    // exact, the scaling being a power of two, except that 2^128 overflows
+    (bits as f64 * 2f64.powi(shift as i32)) as f32
+}
+
+
+/// Formats like C's `printf("%.2f", x)`.
+fn format_float(value: f32) -> String {
+    if value.is_nan() {
+        return if value.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if value.is_infinite() {
+        return if value < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    format!("{:.2}", value as f64)
+}
@@ -0,0 +1,608 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cfg::{self, Cfg, EdgeKind};
+use crate::ir::{Kind, Op, Operand, Program, Type};
+use crate::value::Value;
+
+/*
+Static single assignment form
+
+Temporaries are already assigned exactly once; variables are not. `construct`
+computes the dominator tree, places a phi for every variable at the iterated
+dominance frontier of its assignments and renames, giving every assignment
+(and every phi) a version of its own and telling for each read which version
+it sees. The form is kept alongside the IR rather than replacing it: the
+passes below use it to prove facts and then rewrite the ordinary IR, so no
+phis have to be taken out again and the backends never see them.
+
+  - `propagate_conditional_constants` is sparse conditional constant
+    propagation: versions and temporaries start out unknown and only blocks
+    reached through edges that can actually be taken are evaluated, so a
+    variable that stays constant around a loop is found constant.
+  - `number_values` is global value numbering over the dominator tree: a
+    temporary computing what a dominating temporary already holds is replaced
+    by it.
+  - `hoist_invariants` is loop-invariant code motion: arithmetic inside a loop
+    whose operands are not changed by the loop moves in front of its header.
+*/
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Definition {
+    /// The value a variable has before the program assigns it.
+    Entry,
+    /// A store or INPUT, by instruction index.
+    Instruction(usize),
+    /// A phi, by index into `Ssa::phis`.
+    Phi(usize),
+}
+
+pub struct Version {
+    pub variable: String,
+    pub definition: Definition,
+    pub block: usize,
+}
+
+pub struct Phi {
+    pub version: usize,
+    /// The version coming in from each predecessor block.
+    pub sources: Vec<(usize, usize)>,
+}
+
+pub struct Ssa {
+    pub graph: Cfg,
+    pub idom: Vec<Option<usize>>,
+    /// The block each instruction belongs to.
+    pub block_of: Vec<usize>,
+    pub versions: Vec<Version>,
+    pub phis: Vec<Phi>,
+    /// Phis at the start of each block.
+    pub phis_at: Vec<Vec<usize>>,
+    /// The version read by (instruction, operand position).
+    pub reads: HashMap<(usize, usize), usize>,
+    /// The version assigned by a store or INPUT instruction.
+    pub defines: HashMap<usize, usize>,
+}
+
+pub fn construct(program: &Program) -> Ssa {
+    let graph = cfg::build(program);
+    let idom = graph.dominators();
+    let predecessors = graph.predecessors();
+
+    let mut block_of = vec![0; program.instructions.len()];
+    for (index, block) in graph.blocks.iter().enumerate() {
+        for slot in &mut block_of[block.start..block.end] {
+            *slot = index;
+        }
+    }
+
+    // dominance frontiers
+    let mut frontiers: Vec<HashSet<usize>> = vec![HashSet::new(); graph.blocks.len()];
+    for (block, preds) in predecessors.iter().enumerate() {
+        if preds.len() < 2 || idom[block].is_none() {
+            continue;
+        }
+        for pred in preds {
+            let mut runner = *pred;
+            while idom[runner].is_some() && Some(runner) != idom[block] {
+                frontiers[runner].insert(block);
+                if idom[runner] == Some(runner) {
+                    break;
+                }
+                runner = idom[runner].unwrap();
+            }
+        }
+    }
+
+    let mut ssa = Ssa {
+        graph,
+        idom,
+        block_of,
+        versions: vec![],
+        phis: vec![],
+        phis_at: vec![],
+        reads: HashMap::new(),
+        defines: HashMap::new(),
+    };
+    ssa.phis_at = vec![vec![]; ssa.graph.blocks.len()];
+
+    // phi placement
+    let variables = program.variables();
+    for variable in &variables {
+        let mut worklist: Vec<usize> = vec![ssa.graph.entry];
+        for (index, instruction) in program.instructions.iter().enumerate() {
+            match &instruction.kind {
+                Kind::Store { variable: name, .. } | Kind::Input { variable: name } if name == variable => {
+                    worklist.push(ssa.block_of[index]);
+                },
+                _ => {}
+            }
+        }
+        let mut has_phi: HashSet<usize> = HashSet::new();
+        while let Some(block) = worklist.pop() {
+            for frontier in &frontiers[block] {
+                if has_phi.insert(*frontier) {
+                    let version = ssa.versions.len();
+                    ssa.versions.push(Version {
+                        variable: variable.clone(),
+                        definition: Definition::Phi(ssa.phis.len()),
+                        block: *frontier,
+                    });
+                    ssa.phis_at[*frontier].push(ssa.phis.len());
+                    ssa.phis.push(Phi { version, sources: vec![] });
+                    worklist.push(*frontier);
+                }
+            }
+        }
+    }
+
+    // renaming, walking the dominator tree from the entry
+    let mut children: Vec<Vec<usize>> = vec![vec![]; ssa.graph.blocks.len()];
+    for (block, parent) in ssa.idom.iter().enumerate() {
+        if let Some(parent) = parent {
+            if *parent != block {
+                children[*parent].push(block);
+            }
+        }
+    }
+    let mut current: HashMap<String, Vec<usize>> = HashMap::new();
+    for variable in variables {
+        current.insert(variable.clone(), vec![ssa.versions.len()]);
+        ssa.versions.push(Version { variable, definition: Definition::Entry, block: ssa.graph.entry });
+    }
+    let entry = ssa.graph.entry;
+    rename(&mut ssa, program, &children, entry, &mut current);
+    ssa
+}
+
+fn rename(ssa: &mut Ssa, program: &Program, children: &[Vec<usize>], block: usize, current: &mut HashMap<String, Vec<usize>>) {
+    let mut pushed: Vec<String> = vec![];
+
+    for phi in ssa.phis_at[block].clone() {
+        let version = ssa.phis[phi].version;
+        let variable = ssa.versions[version].variable.clone();
+        current.get_mut(&variable).unwrap().push(version);
+        pushed.push(variable);
+    }
+
+    let (start, end) = (ssa.graph.blocks[block].start, ssa.graph.blocks[block].end);
+    for index in start..end {
+        let instruction = &program.instructions[index];
+        for (position, operand) in instruction.operands().into_iter().enumerate() {
+            if let Operand::Variable(name) = operand {
+                ssa.reads.insert((index, position), *current[name].last().unwrap());
+            }
+        }
+        if let Kind::Store { variable, .. } | Kind::Input { variable } = &instruction.kind {
+            let version = ssa.versions.len();
+            ssa.versions.push(Version {
+                variable: variable.clone(),
+                definition: Definition::Instruction(index),
+                block,
+            });
+            ssa.defines.insert(index, version);
+            current.get_mut(variable).unwrap().push(version);
+            pushed.push(variable.clone());
+        }
+    }
+
+    for edge in ssa.graph.blocks[block].successors.clone() {
+        for phi in ssa.phis_at[edge.target].clone() {
+            let variable = &ssa.versions[ssa.phis[phi].version].variable;
+            let version = *current[variable].last().unwrap();
+            ssa.phis[phi].sources.push((block, version));
+        }
+    }
+
+    for child in &children[block] {
+        rename(ssa, program, children, *child, current);
+    }
+
+    for variable in pushed {
+        current.get_mut(&variable).unwrap().pop();
+    }
+}
+
+impl Ssa {
+    fn definition_block(&self, version: usize) -> usize {
+        self.versions[version].block
+    }
+}
+
+/*
+Sparse conditional constant propagation
+*/
+
+#[derive(Copy, Clone)]
+enum Lattice {
+    Unknown,
+    Constant(Value),
+    Varying,
+}
+
+/// Constants compare by spelling, so a NaN cell equals itself and the
+/// propagation settles.
+impl PartialEq for Lattice {
+    fn eq(&self, other: &Lattice) -> bool {
+        match (self, other) {
+            (Lattice::Constant(l), Lattice::Constant(r)) => l.literal() == r.literal(),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+fn meet(left: Lattice, right: Lattice) -> Lattice {
+    match (left, right) {
+        (Lattice::Unknown, other) | (other, Lattice::Unknown) => other,
+        // compare spellings so 0.0 and -0.0 stay apart
+        (Lattice::Constant(l), Lattice::Constant(r)) if l.literal() == r.literal() => left,
+        _ => Lattice::Varying,
+    }
+}
+
+struct Propagation<'a> {
+    ssa: &'a Ssa,
+    temps: Vec<Lattice>,
+    versions: Vec<Lattice>,
+    executable: HashSet<(usize, usize)>,
+    reached: Vec<bool>,
+    changed: bool,
+}
+
+impl Propagation<'_> {
+    fn operand(&self, operand: &Operand, index: usize, position: usize) -> Lattice {
+        match operand {
+            Operand::Constant(value) => Lattice::Constant(*value),
+            Operand::Temp(temp) => self.temps[*temp],
+            Operand::Variable(_) => self.versions[self.ssa.reads[&(index, position)]],
+        }
+    }
+
+    fn lower_temp(&mut self, temp: usize, value: Lattice) {
+        let lowered = meet(self.temps[temp], value);
+        if lowered != self.temps[temp] {
+            self.temps[temp] = lowered;
+            self.changed = true;
+        }
+    }
+
+    fn lower_version(&mut self, version: usize, value: Lattice) {
+        let lowered = meet(self.versions[version], value);
+        if lowered != self.versions[version] {
+            self.versions[version] = lowered;
+            self.changed = true;
+        }
+    }
+
+    fn take(&mut self, block: usize, target: usize) {
+        if self.executable.insert((block, target)) {
+            self.changed = true;
+        }
+        if !self.reached[target] {
+            self.reached[target] = true;
+            self.changed = true;
+        }
+    }
+}
+
+pub fn propagate_conditional_constants(program: &mut Program) -> bool {
+    let ssa = construct(program);
+    let order = ssa.graph.reverse_postorder();
+    let mut state = Propagation {
+        ssa: &ssa,
+        temps: vec![Lattice::Unknown; program.temps.len()],
+        versions: vec![Lattice::Unknown; ssa.versions.len()],
+        executable: HashSet::new(),
+        reached: vec![false; ssa.graph.blocks.len()],
+        changed: true,
+    };
+    state.reached[ssa.graph.entry] = true;
+    for (version, info) in ssa.versions.iter().enumerate() {
+        if info.definition == Definition::Entry {
+            state.versions[version] = Lattice::Varying;
+        }
+    }
+
+    while state.changed {
+        state.changed = false;
+        for block in &order {
+            if !state.reached[*block] {
+                continue;
+            }
+            for phi in &ssa.phis_at[*block] {
+                let phi = &ssa.phis[*phi];
+                for (source_block, source) in &phi.sources {
+                    if state.executable.contains(&(*source_block, *block)) {
+                        state.lower_version(phi.version, state.versions[*source]);
+                    }
+                }
+            }
+
+            let range = ssa.graph.blocks[*block].start..ssa.graph.blocks[*block].end;
+            for index in range {
+                let instruction = &program.instructions[index];
+                match &instruction.kind {
+                    Kind::Binary { dest, op, left, right } => {
+                        let value = match (state.operand(left, index, 0), state.operand(right, index, 1)) {
+                            (Lattice::Constant(l), Lattice::Constant(r)) => match op.evaluate(l, r) {
+                                Ok(value) => Lattice::Constant(value),
+                                Err(_) => Lattice::Varying,
+                            },
+                            (Lattice::Unknown, _) | (_, Lattice::Unknown) => Lattice::Unknown,
+                            _ => Lattice::Varying,
+                        };
+                        state.lower_temp(*dest, value);
+                    },
+                    Kind::Negate { dest, operand } => {
+                        let value = match state.operand(operand, index, 0) {
+                            Lattice::Constant(value) => value.negate().map_or(Lattice::Varying, Lattice::Constant),
+                            other => other,
+                        };
+                        state.lower_temp(*dest, value);
+                    },
+                    Kind::Store { value, .. } => {
+                        let value = match state.operand(value, index, 0) {
+                            Lattice::Constant(value) => Lattice::Constant(value.as_float()),
+                            other => other,
+                        };
+                        state.lower_version(ssa.defines[&index], value);
+                    },
+                    Kind::Input { .. } => state.lower_version(ssa.defines[&index], Lattice::Varying),
+                    _ => {}
+                }
+            }
+
+            let successors = &ssa.graph.blocks[*block].successors;
+            let last = ssa.graph.blocks[*block].end.checked_sub(1).map(|index| (index, &program.instructions[index]));
+            let condition = match last {
+                Some((index, instruction)) => match &instruction.kind {
+                    Kind::JumpIfZero { condition, .. } => Some(state.operand(condition, index, 0)),
+                    _ => None,
+                },
+                None => None,
+            };
+            for edge in successors {
+                let feasible = match condition {
+                    None | Some(Lattice::Varying) => true,
+                    Some(Lattice::Unknown) => false,
+                    Some(Lattice::Constant(value)) => (value.as_f64() != 0.0) == (edge.kind == EdgeKind::True),
+                };
+                if feasible {
+                    state.take(*block, edge.target);
+                }
+            }
+        }
+    }
+
+    let mut changed = false;
+    for (index, instruction) in program.instructions.iter_mut().enumerate() {
+        if !state.reached[ssa.block_of[index]] {
+            continue;
+        }
+        for (position, operand) in instruction.operands_mut().into_iter().enumerate() {
+            let value = match operand {
+                Operand::Constant(_) => continue,
+                Operand::Temp(temp) => state.temps[*temp],
+                Operand::Variable(_) => state.versions[ssa.reads[&(index, position)]],
+            };
+            if let Lattice::Constant(value) = value {
+                *operand = Operand::Constant(value);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/*
+Global value numbering
+*/
+
+pub fn number_values(program: &mut Program) -> bool {
+    let ssa = construct(program);
+    let mut children: Vec<Vec<usize>> = vec![vec![]; ssa.graph.blocks.len()];
+    for (block, parent) in ssa.idom.iter().enumerate() {
+        if let Some(parent) = parent {
+            if *parent != block {
+                children[*parent].push(block);
+            }
+        }
+    }
+
+    let mut numbering = Numbering {
+        ssa: &ssa,
+        version_keys: HashMap::new(),
+        available: HashMap::new(),
+        replaced: HashMap::new(),
+        changed: false,
+    };
+    numbering.visit(program, &children, ssa.graph.entry);
+    numbering.changed
+}
+
+struct Numbering<'a> {
+    ssa: &'a Ssa,
+    /// What each version is known to be equal to, as a key like the ones below.
+    version_keys: HashMap<usize, String>,
+    /// Expression key to the temporary that computes it, in the dominating blocks.
+    available: HashMap<String, usize>,
+    /// Temporaries replaced by an earlier one holding the same value.
+    replaced: HashMap<usize, usize>,
+    changed: bool,
+}
+
+impl Numbering<'_> {
+    fn key(&self, program: &Program, index: usize, position: usize) -> String {
+        match program.instructions[index].operands()[position] {
+            Operand::Constant(value) => value.literal(),
+            Operand::Temp(temp) => format!("%{}", temp),
+            Operand::Variable(_) => {
+                let version = self.ssa.reads[&(index, position)];
+                self.version_keys.get(&version).cloned().unwrap_or_else(|| format!("v{}", version))
+            },
+        }
+    }
+
+    fn visit(&mut self, program: &mut Program, children: &[Vec<usize>], block: usize) {
+        let mut added: Vec<String> = vec![];
+        let (start, end) = (self.ssa.graph.blocks[block].start, self.ssa.graph.blocks[block].end);
+        for index in start..end {
+            for operand in program.instructions[index].operands_mut() {
+                if let Operand::Temp(temp) = operand {
+                    if let Some(earlier) = self.replaced.get(temp) {
+                        *temp = *earlier;
+                        self.changed = true;
+                    }
+                }
+            }
+
+            let key = match &program.instructions[index].kind {
+                Kind::Binary { dest, op, .. } => {
+                    let mut operands = [self.key(program, index, 0), self.key(program, index, 1)];
+                    if matches!(op, Op::Add | Op::Mul | Op::Eq | Op::Ne) {
+                        operands.sort();
+                    }
+                    Some((*dest, format!("{} {} {}", operands[0], op.symbol(), operands[1])))
+                },
+                Kind::Negate { dest, .. } => Some((*dest, format!("-{}", self.key(program, index, 0)))),
+                Kind::Store { value, .. } => {
+                    // a store converts to float, so other values do not keep their identity
+                    let key = match value {
+                        Operand::Constant(value) => Some(value.as_float().literal()),
+                        _ if program.operand_type(value) == Type::Float => Some(self.key(program, index, 0)),
+                        _ => None,
+                    };
+                    if let Some(key) = key {
+                        self.version_keys.insert(self.ssa.defines[&index], key);
+                    }
+                    None
+                },
+                _ => None,
+            };
+            if let Some((dest, key)) = key {
+                match self.available.get(&key) {
+                    Some(earlier) => {
+                        self.replaced.insert(dest, *earlier);
+                    },
+                    None => {
+                        self.available.insert(key.clone(), dest);
+                        added.push(key);
+                    },
+                }
+            }
+        }
+
+        for child in &children[block] {
+            self.visit(program, children, *child);
+        }
+        for key in added {
+            self.available.remove(&key);
+        }
+    }
+}
+
+/*
+Loop-invariant code motion
+
+A loop is found from a back edge, a jump to a block that dominates it. Code
+is only hoisted when the loop header has a single way in from outside, by
+falling through from the instruction right before it, so the hoisted code can
+simply go in front of the header's label. Integer arithmetic stays put, since
+overflow or division by zero in C is undefined even where the loop would never
+have run it.
+*/
+
+pub fn hoist_invariants(program: &mut Program) -> bool {
+    let ssa = construct(program);
+    let predecessors = ssa.graph.predecessors();
+
+    for (header, preds) in predecessors.iter().enumerate() {
+        if ssa.idom[header].is_none() || header == ssa.graph.entry {
+            continue;
+        }
+        let latches: Vec<usize> = preds.iter()
+            .copied()
+            .filter(|pred| ssa.idom[*pred].is_some() && Cfg::dominates(&ssa.idom, header, *pred))
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+
+        let mut body: HashSet<usize> = HashSet::from([header]);
+        let mut worklist = latches;
+        while let Some(block) = worklist.pop() {
+            if ssa.idom[block].is_some() && body.insert(block) {
+                worklist.extend(&predecessors[block]);
+            }
+        }
+
+        // the only way in must be falling through into the header's label
+        let start = ssa.graph.blocks[header].start;
+        let entries: Vec<usize> = preds.iter().copied().filter(|pred| !body.contains(pred)).collect();
+        let single_entry = entries.len() == 1
+            && ssa.graph.blocks[entries[0]].end == start
+            && ssa.graph.blocks[entries[0]].successors.iter()
+                .all(|edge| edge.target != header || edge.kind == EdgeKind::Fallthrough);
+        let back_edges_jump = body.iter().all(|block| {
+            ssa.graph.blocks[*block].successors.iter()
+                .all(|edge| edge.target != header || matches!(edge.kind, EdgeKind::Jump | EdgeKind::False))
+        });
+        if !single_entry || !back_edges_jump || !matches!(program.instructions[start].kind, Kind::Label(_)) {
+            continue;
+        }
+
+        let mut defined_inside: HashSet<usize> = HashSet::new();
+        let mut in_body: Vec<usize> = vec![];
+        for (index, instruction) in program.instructions.iter().enumerate() {
+            if body.contains(&ssa.block_of[index]) {
+                in_body.push(index);
+                if let Some(dest) = instruction.dest() {
+                    defined_inside.insert(dest);
+                }
+            }
+        }
+
+        let mut hoisted: Vec<usize> = vec![];
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for index in &in_body {
+                let instruction = &program.instructions[*index];
+                let Some(dest) = instruction.dest() else {
+                    continue;
+                };
+                if !defined_inside.contains(&dest) {
+                    continue;
+                }
+                if let Kind::Binary { op, .. } = &instruction.kind {
+                    if program.temps[dest] == Type::Int && !op.is_comparison() {
+                        continue;
+                    }
+                }
+                let invariant = instruction.operands().iter().enumerate().all(|(position, operand)| match operand {
+                    Operand::Constant(_) => true,
+                    Operand::Temp(temp) => !defined_inside.contains(temp),
+                    Operand::Variable(_) => !body.contains(&ssa.definition_block(ssa.reads[&(*index, position)])),
+                });
+                if invariant {
+                    defined_inside.remove(&dest);
+                    hoisted.push(*index);
+                    progress = true;
+                }
+            }
+        }
+        if hoisted.is_empty() {
+            continue;
+        }
+
+        let moved: Vec<_> = hoisted.iter().map(|index| program.instructions[*index].clone()).collect();
+        let mut index = 0;
+        program.instructions.retain(|_| {
+            index += 1;
+            !hoisted.contains(&(index - 1))
+        });
+        let start = start - hoisted.iter().filter(|index| **index < start).count();
+        program.instructions.splice(start..start, moved);
+        return true;
+    }
+    false
+}
@@ -0,0 +1,900 @@
+use crate::ir::Type;
+use crate::lex::{Token, TokenType};
+use crate::parse::Node;
+use crate::value::{Fault, Value};
+
+/*
+Python, JavaScript and Rust generation from the AST
+
+Meant to be read: statements map one to one onto the target language,
+WHILE becomes a while loop and IF an if. Every expression keeps the C type
+it has in the generated C, so each language computes what the C does. Rust
+has the same types, `f32` variables among them, and conversions spelled out
+with `as`; Python and JavaScript round float results with `f32()` or
+`Math.fround()`. An `int` that overflows wraps around and, in floating
+point, a division by zero gives infinity or NaN. A small prelude prints
+numbers the way printf does and reads INPUT the way scanf does, a
+whitespace-separated number at a time, giving 0 for a word that is not a
+number and leaving the variable alone at the end of input. Names that are
+reserved in the target get an underscore appended.
+
+None of the languages has goto. When a program uses GOTO or LABEL, the
+statements that contain them are lowered into labels and jumps, the way
+`ir.rs` lowers them, and the result runs as a state machine: a loop around
+one section per label, where jumping sets `state` to the label and starts
+the next iteration. Statements without any GOTO or LABEL inside stay
+structured.
+*/
+
+#[derive(PartialEq, Copy, Clone)]
+enum Language {
+    Python,
+    JavaScript,
+    Rust,
+}
+
+const PYTHON_RESERVED: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+    "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+    "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+    "print", "input", "float", "int", "input_number", "state", "math", "re", "struct", "sys", "f32", "div",
+    "int32", "print_number", "NUMBER", "buffer", "hex_float",
+];
+
+const JAVASCRIPT_RESERVED: &[&str] = &[
+    "await", "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do",
+    "else", "enum", "export", "extends", "false", "finally", "for", "function", "if", "import", "in",
+    "instanceof", "let", "new", "null", "return", "super", "switch", "this", "throw", "true", "try",
+    "typeof", "var", "void", "while", "with", "yield", "arguments", "eval", "undefined", "NaN", "Infinity",
+    "console", "Math", "Number", "parseFloat", "require", "process", "readline", "lines", "inputNumber",
+    "main", "state", "dispatch", "printNumber", "NUMBER", "buffer", "BigInt", "DataView", "ArrayBuffer",
+    "hexFloat",
+];
+
+const RUST_RESERVED: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try", "typeof", "unsized",
+    "virtual", "yield", "io", "std", "f32", "f64", "i32", "main", "input", "input_number", "print_number",
+    "state", "None", "Some", "Ok", "Err", "hex_float",
+];
+
+/// The Python helpers, in the order they are written out, each with the
+/// modules it needs.
+const PYTHON_HELPERS: &[(&str, &[&str], &str)] = &[
+    ("f32", &["math", "struct"], r#"def f32(x):
+    # the nearest C float; struct refuses the values that round to infinity
+    try:
+        return struct.unpack("f", struct.pack("f", x))[0]
+    except OverflowError:
+        return math.copysign(math.inf, x)
+"#),
+    ("div", &["math"], r#"def div(a, b):
+    if b != 0:
+        return a / b
+    if math.isnan(a):
+        return a
+    if a == 0:
+        # the NaN the hardware gives for 0 / 0, as in C
+        return math.inf - math.inf
+    return math.copysign(math.inf, a) * math.copysign(1.0, b)
+"#),
+    ("int32", &[], r#"def int32(n):
+    return (n + 2**31) % 2**32 - 2**31
+"#),
+    ("print_number", &["math"], r#"def print_number(x):
+    if math.isnan(x):
+        print("-nan" if math.copysign(1.0, x) < 0 else "nan")
+    else:
+        print(f"{x:.2f}")
+"#),
+    ("input_number", &["math", "re", "sys"], r#"NUMBER = re.compile(
+    r"[+-]?(?:0x(?:(?:[0-9a-f]+\.?[0-9a-f]*|\.[0-9a-f]+)(?:p[+-]?\d*)?|\.)"
+    r"|(?!0x)(?:\d+\.?\d*|\.\d+)(?:e[+-]?\d+)?|inf(?:inity)?|nan)",
+    re.IGNORECASE,
+)
+buffer = ""
+
+
+def hex_float(text):
+    # the nearest float to a hexadecimal number like 0x1.8p3, rounded to even like strtof
+    sign = -1.0 if text.startswith("-") else 1.0
+    mantissa, _, exponent = text.lower().lstrip("+-")[2:].partition("p")
+    whole, _, fraction = mantissa.partition(".")
+    bits = int(whole + fraction or "0", 16)
+    shift = int(exponent.rstrip("+-") or "0") - 4 * len(fraction)
+    top = bits.bit_length() - 1 + shift
+    if bits == 0 or top < -150:
+        return math.copysign(0.0, sign)
+    if top > 127:
+        return sign * math.inf
+    # 24 significant bits, fewer for a subnormal
+    drop = bits.bit_length() - min(top + 150, 24)
+    if drop > 0:
+        kept, rest, half = bits >> drop, bits & ((1 << drop) - 1), 1 << (drop - 1)
+        bits = kept + (rest > half or rest == half and kept & 1)
+        shift += drop
+    return sign * math.ldexp(bits, shift)
+
+
+def input_number(current):
+    global buffer
+    buffer = buffer.lstrip()
+    while not buffer:
+        buffer = sys.stdin.readline()
+        if not buffer:
+            return current
+        buffer = buffer.lstrip()
+    number = NUMBER.match(buffer)
+    if not number:
+        buffer = re.sub(r"^\S+", "", buffer)
+        return 0.0
+    buffer = buffer[number.end():]
+    text = number.group()
+    return f32(hex_float(text) if "x" in text.lower() else float(text))
+"#),
+];
+
+/// The JavaScript helpers, in the order they are written out.
+const JAVASCRIPT_HELPERS: &[(&str, &str)] = &[
+    ("inputNumber", r#"const readline = require("readline");
+
+const rl = readline.createInterface({ input: process.stdin });
+const lines = rl[Symbol.asyncIterator]();
+const NUMBER = new RegExp(
+  String.raw`^[+-]?(?:0x(?:(?:[0-9a-f]+\.?[0-9a-f]*|\.[0-9a-f]+)(?:p[+-]?\d*)?|\.)` +
+    String.raw`|(?!0x)(?:\d+\.?\d*|\.\d+)(?:e[+-]?\d+)?|inf(?:inity)?|nan)`,
+  "i",
+);
+let buffer = "";
+
+function hexFloat(text) {
+  // the nearest float to a hexadecimal number like 0x1.8p3, rounded to even like strtof
+  const sign = text.startsWith("-") ? -1 : 1;
+  const [mantissa, exponent = ""] = text.replace(/^[+-]?0x/, "").split("p");
+  const [whole, fraction = ""] = mantissa.split(".");
+  let bits = BigInt(`0x0${whole}${fraction}`);
+  let shift = Number(exponent.replace(/[+-]$/, "")) - 4 * fraction.length;
+  const width = bits.toString(2).length;
+  const top = width - 1 + shift;
+  if (bits === 0n || top < -150) {
+    return sign * 0;
+  }
+  if (top > 127) {
+    return sign * Infinity;
+  }
+  // 24 significant bits, fewer for a subnormal
+  const drop = width - Math.min(top + 150, 24);
+  if (drop > 0) {
+    const kept = bits >> BigInt(drop);
+    const rest = bits - (kept << BigInt(drop));
+    const half = 1n << BigInt(drop - 1);
+    bits = kept + (rest > half || (rest === half && kept % 2n === 1n) ? 1n : 0n);
+    shift += drop;
+  }
+  return Math.fround(sign * Number(bits) * 2 ** shift);
+}
+
+async function inputNumber(current) {
+  buffer = buffer.trimStart();
+  while (buffer === "") {
+    const { value, done } = await lines.next();
+    if (done) {
+      return current;
+    }
+    buffer = value.trimStart();
+  }
+  const number = NUMBER.exec(buffer);
+  if (number === null) {
+    buffer = buffer.replace(/^\S+/, "");
+    return 0;
+  }
+  buffer = buffer.slice(number[0].length);
+  const text = number[0].toLowerCase();
+  if (text.includes("x")) {
+    return hexFloat(text);
+  }
+  return text.endsWith("nan") ? NaN : Math.fround(Number(text.replace(/inf(inity)?$/, "Infinity")));
+}
+"#),
+    ("printNumber", r#"function printNumber(x) {
+  const bits = new DataView(new ArrayBuffer(8));
+  bits.setFloat64(0, x);
+  const sign = bits.getUint8(0) & 0x80 ? "-" : "";
+  const magnitude = Math.abs(x);
+  let digits;
+  if (Number.isNaN(x)) {
+    digits = "nan";
+  } else if (magnitude === Infinity) {
+    digits = "inf";
+  } else if (magnitude >= 1e21) {
+    // toFixed would switch to an exponent
+    digits = `${BigInt(magnitude)}.00`;
+  } else if (magnitude * 8 % 2 === 1 && "02468".includes(magnitude.toFixed(3).slice(-2, -1))) {
+    // an exact half, which toFixed rounds up and printf to even
+    digits = magnitude.toFixed(3).slice(0, -1);
+  } else {
+    digits = magnitude.toFixed(2);
+  }
+  console.log(sign + digits);
+}
+"#),
+];
+
+/// The Rust helpers, in the order they are written out.
+const RUST_HELPERS: &[(&str, &str)] = &[
+    ("input_number", r#"/// Reads a number like scanf("%f"): 0 for a word that is not a number, None
+/// at the end of input.
+fn input_number(buffer: &mut String) -> Option<f32> {
+    loop {
+        let blank = buffer.len() - buffer.trim_start().len();
+        buffer.drain(..blank);
+        if !buffer.is_empty() {
+            break;
+        }
+        if io::stdin().read_line(buffer).ok()? == 0 {
+            return None;
+        }
+    }
+    // the longest number the next word starts with
+    let word = buffer.split_whitespace().next().unwrap().len();
+    let number = hex_float(&buffer[..word]).or_else(|| {
+        (1..=word).rev().filter(|end| buffer.is_char_boundary(*end))
+            .find_map(|end| Some((end, buffer[..end].parse().ok()?)))
+    });
+    let (end, number) = number.unwrap_or((word, 0.0));
+    buffer.drain(..end);
+    Some(number)
+}
+
+/// Reads a hexadecimal number like 0x1.8p3 at the start of `word`, which
+/// glibc's scanf accepts: its length and the nearest float, rounded to even.
+fn hex_float(word: &str) -> Option<(usize, f32)> {
+    let bytes = word.as_bytes();
+    let mut end = usize::from(word.starts_with(['+', '-']));
+    if !word.get(end..end + 2)?.eq_ignore_ascii_case("0x") {
+        return None;
+    }
+    end += 2;
+    let (mut bits, mut sticky, mut shift, mut digits, mut point) = (0u64, false, 0i64, 0, false);
+    while let Some(&byte) = bytes.get(end) {
+        match (byte as char).to_digit(16) {
+            None if byte == b'.' && !point => point = true,
+            None => break,
+            Some(digit) if bits >> 60 == 0 => {
+                bits = bits << 4 | u64::from(digit);
+                shift -= if point { 4 } else { 0 };
+                digits += 1;
+            },
+            // digits past 60 bits only matter when rounding a tie
+            Some(digit) => {
+                sticky |= digit != 0;
+                shift += if point { 0 } else { 4 };
+                digits += 1;
+            },
+        }
+        end += 1;
+    }
+    if digits == 0 && !point {
+        return Some((word.len(), 0.0));
+    }
+    if digits > 0 && matches!(bytes.get(end), Some(b'p' | b'P')) {
+        end += 1;
+        let negative = bytes.get(end) == Some(&b'-');
+        end += usize::from(matches!(bytes.get(end), Some(b'+' | b'-')));
+        let mut exponent = 0i64;
+        while let Some(digit) = bytes.get(end).filter(|byte| byte.is_ascii_digit()) {
+            // far beyond the exponents that give 0 or infinity
+            exponent = (exponent * 10 + i64::from(digit - b'0')).min(1 << 20);
+            end += 1;
+        }
+        shift += if negative { -exponent } else { exponent };
+    }
+    let bits = u128::from(bits | u64::from(sticky));
+    let width = 128 - i64::from(bits.leading_zeros());
+    let top = width - 1 + shift;
+    let magnitude = if bits == 0 || top < -150 {
+        0.0
+    } else if top > 127 {
+        f32::INFINITY
+    } else {
+        // 24 significant bits, fewer for a subnormal
+        let drop = (width - (top + 150).min(24)).max(0);
+        let (kept, rest, half) = (bits >> drop, bits & ((1 << drop) - 1), (1 << drop) >> 1);
+        let kept = kept + u128::from(drop > 0 && (rest > half || rest == half && kept & 1 == 1));
+        (kept as f64 * 2f64.powi((shift + drop) as i32)) as f32
+    };
+    Some((end, if word.starts_with('-') { -magnitude } else { magnitude }))
+}
+"#),
+    ("print_number", r#"fn print_number(x: f32) {
+    if x.is_nan() {
+        println!("{}nan", if x.is_sign_negative() { "-" } else { "" });
+    } else {
+        println!("{:.2}", x);
+    }
+}
+"#),
+];
+
+/// Spells a Rust literal of the value's type, `f32` or `f64`.
+fn rust_literal(value: Value) -> String {
+    let (kind, number) = match value {
+        Value::Float(number) => ("f32", number as f64),
+        _ => ("f64", value.as_f64()),
+    };
+    match value {
+        _ if number.is_nan() => format!("{}::NAN", kind),
+        _ if number.is_infinite() => format!("{}{}::INFINITY", if number < 0.0 { "-" } else { "" }, kind),
+        Value::Float(number) => format!("{:?}", number),
+        _ => format!("{:?}", number),
+    }
+}
+
+/// A statement after GOTO and LABEL have been lowered out of IF and WHILE.
+enum Flat<'a> {
+    /// A statement with no GOTO or LABEL inside, written out structured.
+    Statement(&'a Node),
+    Label(String),
+    Jump(String),
+    JumpUnless(&'a Node, String),
+}
+
+/// An expression written out in the target language.
+struct Expression {
+    text: String,
+    /// Its type in the C, which decides how it is rounded.
+    kind: Type,
+    /// Its value when it only has numbers in it, as every `int` but a
+    /// comparison's result does.
+    value: Option<Value>,
+    /// Whether it can be an operand without parentheses.
+    atom: bool,
+}
+
+pub fn python(root: &Node) -> String {
+    Transpiler::new(Language::Python).program(root)
+}
+
+pub fn javascript(root: &Node) -> String {
+    Transpiler::new(Language::JavaScript).program(root)
+}
+
+pub fn rust(root: &Node) -> String {
+    Transpiler::new(Language::Rust).program(root)
+}
+
+struct Transpiler {
+    language: Language,
+    code: String,
+    depth: usize,
+    next_label: usize,
+    /// The prelude functions the code calls.
+    helpers: Vec<&'static str>,
+}
+
+fn has_jumps(node: &Node) -> bool {
+    matches!(node.token.kind, TokenType::GOTO | TokenType::LABEL) || node.children.iter().any(has_jumps)
+}
+
+fn uses_input(node: &Node) -> bool {
+    node.token.kind == TokenType::INPUT || node.children.iter().any(uses_input)
+}
+
+fn variables(node: &Node, names: &mut Vec<String>) {
+    if matches!(node.token.kind, TokenType::LET | TokenType::INPUT) {
+        let name = &node.children[0].token.text;
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    for child in &node.children {
+        variables(child, names);
+    }
+}
+
+impl Transpiler {
+    fn new(language: Language) -> Self {
+        Transpiler { language, code: String::new(), depth: 0, next_label: 0, helpers: vec![] }
+    }
+
+    fn line(&mut self, line: &str) {
+        let width = if self.language == Language::JavaScript { 2 } else { 4 };
+        self.code += &" ".repeat(width * self.depth);
+        self.code += line;
+        self.code += "\n";
+    }
+
+    /// Ends a statement, with a semicolon where the language wants one.
+    fn statement_line(&mut self, statement: &str) {
+        match self.language {
+            Language::Python => self.line(statement),
+            _ => self.line(&format!("{};", statement)),
+        }
+    }
+
+    fn name(&self, name: &str) -> String {
+        let reserved = match self.language {
+            Language::Python => PYTHON_RESERVED,
+            Language::JavaScript => JAVASCRIPT_RESERVED,
+            Language::Rust => RUST_RESERVED,
+        };
+        if reserved.contains(&name) {
+            format!("{}_", name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Names a prelude function, which is then written out.
+    fn helper(&mut self, name: &'static str) -> &'static str {
+        if !self.helpers.contains(&name) {
+            self.helpers.push(name);
+        }
+        name
+    }
+
+    fn program(mut self, root: &Node) -> String {
+        let mut names = vec![];
+        variables(root, &mut names);
+        let input = uses_input(root);
+
+        match self.language {
+            Language::Python => {},
+            Language::JavaScript => self.line("async function main() {"),
+            Language::Rust => self.line("fn main() {"),
+        }
+        if self.language != Language::Python {
+            self.depth += 1;
+        }
+        if input && self.language == Language::Rust {
+            self.line("let mut input = String::new();");
+        }
+        for name in &names {
+            let name = self.name(name);
+            match self.language {
+                Language::Python => self.line(&format!("{} = 0.0", name)),
+                Language::JavaScript => self.line(&format!("let {} = 0;", name)),
+                Language::Rust => self.line(&format!("let mut {}: f32 = 0.0;", name)),
+            }
+        }
+        if !names.is_empty() {
+            self.code += "\n";
+        }
+
+        if has_jumps(root) {
+            let mut flat = vec![];
+            self.flatten(&root.children, &mut flat);
+            self.state_machine(&flat);
+        } else {
+            self.statements(&root.children);
+        }
+
+        if self.language != Language::Python {
+            self.depth -= 1;
+            self.line("}");
+        }
+        if self.language == Language::JavaScript {
+            self.code += "\n";
+            if input {
+                self.line("main().finally(() => rl.close());");
+            } else {
+                self.line("main();");
+            }
+        }
+        // the prelude depends on what the program turned out to call
+        let body = std::mem::take(&mut self.code);
+        self.prelude(input);
+        self.code + &body
+    }
+
+    fn prelude(&mut self, input: bool) {
+        let prelude = match (self.language, input) {
+            (Language::Python, _) => {
+                let helpers: Vec<_> = PYTHON_HELPERS.iter().filter(|(name, _, _)| self.helpers.contains(name)).collect();
+                let mut modules: Vec<&str> = helpers.iter().flat_map(|(_, modules, _)| modules.iter().copied()).collect();
+                modules.sort();
+                modules.dedup();
+                let mut prelude: String = modules.iter().map(|module| format!("import {}\n", module)).collect();
+                for (_, _, code) in helpers {
+                    prelude += "\n\n";
+                    prelude += code;
+                }
+                if !prelude.is_empty() {
+                    prelude += "\n\n";
+                }
+                prelude
+            },
+            (Language::JavaScript, _) => {
+                let helpers = JAVASCRIPT_HELPERS.iter().filter(|(name, _)| self.helpers.contains(name));
+                helpers.map(|(_, code)| format!("{}\n", code)).collect()
+            },
+            (Language::Rust, _) => {
+                // the BASIC may well have variables it never reads and code after a GOTO
+                let mut prelude = String::from("#![allow(unused_assignments, unused_variables, unreachable_code)]\n\n");
+                if input {
+                    prelude += "use std::io;\n\n";
+                }
+                for (_, code) in RUST_HELPERS.iter().filter(|(name, _)| self.helpers.contains(name)) {
+                    prelude += code;
+                    prelude += "\n";
+                }
+                prelude
+            },
+        };
+        self.code += &prelude;
+    }
+
+    fn statements(&mut self, statements: &[Node]) {
+        if statements.is_empty() && self.language == Language::Python {
+            self.line("pass");
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn open(&mut self, header: &str) {
+        match self.language {
+            Language::Python => self.line(&format!("{}:", header)),
+            _ => self.line(&format!("{} {{", header)),
+        }
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        if self.language != Language::Python {
+            self.line("}");
+        }
+    }
+
+    /// The header of an if or while statement.
+    fn header(&self, keyword: &str, condition: &str) -> String {
+        match self.language {
+            Language::JavaScript => format!("{} ({})", keyword, condition),
+            _ => format!("{} {}", keyword, condition),
+        }
+    }
+
+    fn statement(&mut self, node: &Node) {
+        match node.token.kind {
+            TokenType::PRINT => {
+                let child = &node.children[0];
+                let text = &child.token.text;
+                let line = match (child.token.kind, self.language) {
+                    (TokenType::STRING, Language::Python) => format!("print(\"{}\")", text),
+                    (TokenType::STRING, Language::JavaScript) => format!("console.log(\"{}\");", text),
+                    (TokenType::STRING, Language::Rust) => {
+                        format!("println!(\"{}\");", text.replace('{', "{{").replace('}', "}}"))
+                    },
+                    (_, Language::Python) => {
+                        let value = self.expression(child);
+                        format!("{}({})", self.helper("print_number"), self.float(value))
+                    },
+                    (_, Language::JavaScript) => {
+                        let value = self.expression(child);
+                        format!("{}({});", self.helper("printNumber"), self.float(value))
+                    },
+                    (_, Language::Rust) => {
+                        let value = self.expression(child);
+                        format!("{}({});", self.helper("print_number"), self.float(value))
+                    },
+                };
+                self.line(&line);
+            },
+            TokenType::IF | TokenType::WHILE => {
+                let keyword = if node.token.kind == TokenType::IF { "if" } else { "while" };
+                let condition = self.expression(&node.children[0]).text;
+                self.open(&self.header(keyword, &condition));
+                self.statements(&node.children[1..]);
+                self.close();
+            },
+            TokenType::LET => {
+                let name = self.name(&node.children[0].token.text);
+                let value = self.expression(&node.children[1]);
+                let value = self.float(value);
+                self.statement_line(&format!("{} = {}", name, value));
+            },
+            TokenType::INPUT => {
+                let name = self.name(&node.children[0].token.text);
+                match self.language {
+                    Language::Python => {
+                        // it rounds what it reads to a float
+                        self.helper("f32");
+                        let input = self.helper("input_number");
+                        self.line(&format!("{0} = {1}({0})", name, input));
+                    },
+                    Language::JavaScript => {
+                        let input = self.helper("inputNumber");
+                        self.line(&format!("{0} = await {1}({0});", name, input));
+                    },
+                    Language::Rust => {
+                        let input = self.helper("input_number");
+                        self.line(&format!("{0} = {1}(&mut input).unwrap_or({0});", name, input));
+                    },
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Rounds a result to a C float.
+    fn round(&mut self, text: &str) -> String {
+        match self.language {
+            Language::Python => format!("{}({})", self.helper("f32"), text),
+            Language::JavaScript => format!("Math.fround({})", text),
+            Language::Rust => format!("({}) as f32", text),
+        }
+    }
+
+    /// Converts an operand to the C type `kind` of its operation. A constant
+    /// becomes the value C converts it to; Rust needs the conversion spelled out.
+    fn convert(&self, expression: Expression, kind: Type) -> Expression {
+        if expression.kind == kind || kind == Type::Int {
+            return expression;
+        }
+        let value = expression.value.map(|value| match kind {
+            Type::Float => value.as_float(),
+            _ => Value::Double(value.as_f64()),
+        });
+        let rust = if kind == Type::Float { "f32" } else { "f64" };
+        let (text, atom) = match (self.language, expression.value, value) {
+            (Language::Rust, _, Some(value)) if expression.text.parse::<i32>().is_ok() => {
+                let text = rust_literal(value);
+                (text.clone(), !text.starts_with('-'))
+            },
+            // a cast followed by `<` would start generic arguments
+            (Language::Rust, _, _) if expression.atom => (format!("({} as {})", expression.text, rust), true),
+            (Language::Rust, _, _) => (format!("(({}) as {})", expression.text, rust), true),
+            // JavaScript gets -0 from `0 * -1` or `-0`, where C's int is 0
+            (Language::JavaScript, Some(Value::Int(0)), _) => ("0".to_string(), true),
+            (_, Some(before), Some(after)) if after.as_f64() != before.as_f64() => {
+                let text = format!("{:?}", after.as_f64());
+                (text.clone(), !text.starts_with('-'))
+            },
+            _ => (expression.text, expression.atom),
+        };
+        Expression { text, kind, value, atom }
+    }
+
+    /// An expression as the C float it becomes in a variable or when printed.
+    fn float(&mut self, expression: Expression) -> String {
+        let exact = expression.value.is_some_and(|value| {
+            value.as_float().as_f64() == value.as_f64() || value.as_f64().is_nan()
+        });
+        let literal = expression.text.parse::<f64>().is_ok() || expression.text.trim_start_matches('-').starts_with("f64::");
+        match (expression.kind, self.language, expression.value) {
+            (Type::Float, _, _) => expression.text,
+            (Type::Int, Language::Rust, _) if expression.text.parse::<i32>().is_err() => match expression.atom {
+                true => format!("{} as f32", expression.text),
+                false => self.round(&expression.text),
+            },
+            // a Python int stays an int, which has no -0.0
+            (Type::Int, Language::Python, Some(value)) => format!("{:?}", value.as_float().as_f64()),
+            (Type::Int, Language::Python, None) => format!("float({})", expression.text),
+            (Type::Int, _, _) => self.convert(expression, Type::Float).text,
+            (Type::Double, Language::Rust, Some(value)) if literal => rust_literal(value.as_float()),
+            (Type::Double, Language::Python | Language::JavaScript, _) if exact => expression.text,
+            (Type::Double, _, _) => self.round(&expression.text),
+        }
+    }
+
+    fn expression(&mut self, node: &Node) -> Expression {
+        match node.token.kind {
+            TokenType::NUMBER => {
+                let value = Value::parse(&node.token.text).unwrap();
+                let text = match (value, self.language) {
+                    (Value::Int(value), _) => value.to_string(),
+                    (_, Language::Python) if value.as_f64().is_nan() => "float(\"nan\")".to_string(),
+                    (_, Language::Python) if value.as_f64().is_infinite() => format!("float(\"{}\")", value.as_f64()),
+                    (_, Language::JavaScript) if value.as_f64().is_nan() => "NaN".to_string(),
+                    (_, Language::JavaScript) if value.as_f64().is_infinite() => "Infinity".to_string(),
+                    (_, Language::Rust) => rust_literal(value),
+                    (Value::Float(value), _) => format!("{:?}", value),
+                    (_, _) => format!("{:?}", value.as_f64()),
+                };
+                Expression { atom: !text.starts_with('-'), text, kind: Type::of(&value), value: Some(value) }
+            },
+            TokenType::IDENT => Expression { text: self.name(&node.token.text), kind: Type::Float, value: None, atom: true },
+            TokenType::PLUS | TokenType::MINUS if node.children.len() == 1 => {
+                let operand = self.expression(&node.children[0]);
+                let text = match node.token.text.as_str() {
+                    "-" if operand.atom => format!("-{}", operand.text),
+                    "-" => format!("-({})", operand.text),
+                    _ => return operand,
+                };
+                Expression { text, value: operand.value.and_then(|value| value.negate()), atom: false, ..operand }
+            },
+            TokenType::EXPRESSION | TokenType::TERM | TokenType::COMPARISON => {
+                let comparison = node.token.kind == TokenType::COMPARISON;
+                let mut left = self.expression(&node.children[0]);
+                for (index, pair) in node.children[1..].chunks(2).enumerate() {
+                    let right = self.expression(&pair[1]);
+                    // the result of a comparison is compared, as in C, rather than chained
+                    if comparison && index > 0 {
+                        let text = match self.language {
+                            Language::Rust => format!("(({}) as i32)", left.text),
+                            _ => format!("({})", left.text),
+                        };
+                        left = Expression { text, kind: Type::Int, value: None, atom: true };
+                    }
+                    left = self.binary(left, &pair[0].token, right, comparison);
+                }
+                left
+            },
+            _ => unreachable!("Unexpected expression node {0}", node.token.text),
+        }
+    }
+
+    /// `left op right` with the operands converted to their common C type.
+    fn binary(&mut self, left: Expression, op: &Token, right: Expression, comparison: bool) -> Expression {
+        let kind = Type::common(left.kind, right.kind);
+        let (left, right) = (self.convert(left, kind), self.convert(right, kind));
+        let text = format!("{} {} {}", left.text, op.text, right.text);
+        if comparison {
+            return Expression { text, kind: Type::Int, value: None, atom: false };
+        }
+
+        let (value, fault) = match (left.value, right.value) {
+            (Some(l), Some(r)) => (Value::arithmetic(op.kind, l, r).ok(), Value::checked(op.kind, l, r).err()),
+            _ => (None, None),
+        };
+        let overflows = fault == Some(Fault::IntegerOverflow);
+        let divides = op.kind == TokenType::SLASH;
+        let (text, atom) = match (self.language, kind) {
+            // rustc refuses to compile constant arithmetic that overflows or divides by zero
+            (Language::Rust, Type::Int) if overflows || fault == Some(Fault::DivisionByZero) => {
+                let method = match op.kind {
+                    TokenType::PLUS => "wrapping_add",
+                    TokenType::MINUS => "wrapping_sub",
+                    TokenType::ASTERISK => "wrapping_mul",
+                    _ => "wrapping_div",
+                };
+                (format!("i32::{}({}, {})", method, left.text, right.text), true)
+            },
+            (Language::Rust, _) => (text, false),
+            (Language::Python, Type::Int) if divides => (format!("int({})", text), true),
+            (Language::JavaScript, Type::Int) if divides => (format!("Math.trunc({})", text), true),
+            // Python only divides by zero in div(), which gives infinity or NaN like C
+            (Language::Python, _) if divides && !right.value.is_some_and(|value| value.as_f64() != 0.0) => {
+                (format!("{}({}, {})", self.helper("div"), left.text, right.text), true)
+            },
+            _ => (text, false),
+        };
+        let (text, atom) = match (self.language, kind) {
+            (Language::Python, Type::Int) if overflows => (format!("{}({})", self.helper("int32"), text), true),
+            (Language::JavaScript, Type::Int) if overflows && op.kind == TokenType::ASTERISK => {
+                (format!("Math.imul({}, {})", left.text, right.text), true)
+            },
+            (Language::JavaScript, Type::Int) if overflows => (format!("(({}) | 0)", text), true),
+            (Language::Python | Language::JavaScript, Type::Float) => (self.round(&text), true),
+            _ => (text, atom),
+        };
+        Expression { text, kind, value, atom }
+    }
+
+    fn label(&mut self, kind: &str) -> String {
+        self.next_label += 1;
+        format!("{}_{}", kind, self.next_label - 1)
+    }
+
+    fn flatten<'a>(&mut self, statements: &'a [Node], flat: &mut Vec<Flat<'a>>) {
+        for statement in statements {
+            match statement.token.kind {
+                TokenType::LABEL => flat.push(Flat::Label(statement.children[0].token.text.clone())),
+                TokenType::GOTO => flat.push(Flat::Jump(statement.children[0].token.text.clone())),
+                TokenType::IF if has_jumps(statement) => {
+                    let end = self.label("endif");
+                    flat.push(Flat::JumpUnless(&statement.children[0], end.clone()));
+                    self.flatten(&statement.children[1..], flat);
+                    flat.push(Flat::Label(end));
+                },
+                TokenType::WHILE if has_jumps(statement) => {
+                    let (start, end) = (self.label("while"), self.label("endwhile"));
+                    flat.push(Flat::Label(start.clone()));
+                    flat.push(Flat::JumpUnless(&statement.children[0], end.clone()));
+                    self.flatten(&statement.children[1..], flat);
+                    flat.push(Flat::Jump(start));
+                    flat.push(Flat::Label(end));
+                },
+                _ => flat.push(Flat::Statement(statement)),
+            }
+        }
+    }
+
+    fn set_state(&mut self, label: &str) {
+        self.statement_line(&format!("state = \"{}\"", label));
+    }
+
+    fn jump(&mut self, label: &str) {
+        self.set_state(label);
+        match self.language {
+            Language::JavaScript => self.line("continue dispatch;"),
+            _ => self.statement_line("continue"),
+        }
+    }
+
+    fn state_machine(&mut self, flat: &[Flat]) {
+        let start = match flat.first() {
+            Some(Flat::Label(label)) => label.clone(),
+            _ if flat.iter().any(|item| matches!(item, Flat::Label(label) if label == "start")) => "start_".to_string(),
+            _ => "start".to_string(),
+        };
+
+        // each section starts at a label, the first one possibly at `start`
+        let mut sections: Vec<(String, Vec<&Flat>)> = vec![(start.clone(), vec![])];
+        for item in flat {
+            match item {
+                Flat::Label(label) if *label == start => {},
+                Flat::Label(label) => sections.push((label.clone(), vec![])),
+                item => sections.last_mut().unwrap().1.push(item),
+            }
+        }
+        // jumping to a state without a section runs off the end, as the program does
+        if sections.len() > 1 && sections.last().unwrap().1.is_empty() {
+            sections.pop();
+        }
+
+        match self.language {
+            Language::Python => self.line(&format!("state = \"{}\"", start)),
+            Language::JavaScript => self.line(&format!("let state = \"{}\";", start)),
+            Language::Rust => self.line(&format!("let mut state = \"{}\";", start)),
+        }
+        match self.language {
+            Language::Python => self.open("while True"),
+            Language::JavaScript => {
+                self.open("dispatch: while (true)");
+                self.open("switch (state)");
+            },
+            Language::Rust => self.open("loop"),
+        }
+
+        for (index, (label, items)) in sections.iter().enumerate() {
+            match self.language {
+                Language::JavaScript => {
+                    self.line(&format!("case \"{}\":", label));
+                    self.depth += 1;
+                },
+                _ => self.open(&format!("if state == \"{}\"", label)),
+            }
+            if self.language == Language::Python && items.is_empty() {
+                self.line("pass");
+            }
+            for item in items {
+                match item {
+                    Flat::Statement(statement) => self.statement(statement),
+                    Flat::Jump(target) => self.jump(target),
+                    Flat::JumpUnless(condition, target) => {
+                        let condition = self.expression(condition).text;
+                        match self.language {
+                            Language::Python => self.open(&format!("if not ({})", condition)),
+                            _ => self.open(&self.header("if", &format!("!({})", condition))),
+                        }
+                        self.jump(target);
+                        self.close();
+                    },
+                    Flat::Label(_) => unreachable!("Labels start sections"),
+                }
+            }
+            let falls_through = !matches!(items.last(), Some(Flat::Jump(_)));
+            if let (true, Some((next, _))) = (falls_through, sections.get(index + 1)) {
+                match self.language {
+                    Language::JavaScript => self.line("// falls through"),
+                    _ => self.set_state(next),
+                }
+            }
+            match self.language {
+                Language::JavaScript => self.depth -= 1,
+                _ => self.close(),
+            }
+        }
+
+        if self.language == Language::JavaScript {
+            self.close();
+        }
+        self.statement_line("break");
+        self.close();
+    }
+}
@@ -0,0 +1,244 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::lex::Lexer;
+use crate::lex::TokenType;
+
+/*
+Numeric values with the semantics of the generated C.
+
+Integer literals are 32-bit C `int`s, literals with a decimal point or
+exponent are `double`s and every BASIC variable is a `float`. An integer
+literal too large for an `int` is read as a `double`, so the C never needs a
+`long`. Binary operators follow C's usual arithmetic conversions, so `1 / 3`
+is integer division while `1.0 / 3` and `x / 3` are not.
+
+At run time `int` arithmetic wraps around, as it does in the C the compiler
+emits, but folding refuses to compute an overflowing `int` expression and
+leaves it to the program.
+
+C has no literals for infinity and NaN, so those are spelled with the
+`INFINITY` and `NAN` macros from <math.h>, cast to double where needed.
+*/
+
+/// The smallest `int`, which has no literal of its own.
+const INT_MIN: &str = "(-2147483647 - 1)";
+
+/// Why an operation on constants has no well-defined finite result.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Fault {
+    DivisionByZero,
+    IntegerOverflow,
+    FloatOverflow,
+    NotANumber,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::DivisionByZero => write!(f, "division by zero"),
+            Fault::IntegerOverflow => write!(f, "integer overflow"),
+            Fault::FloatOverflow => write!(f, "floating-point overflow"),
+            Fault::NotANumber => write!(f, "result is not a number"),
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Value {
+    Int(i32),
+    Float(f32),
+    Double(f64),
+}
+
+impl Value {
+    /// Reads back a NUMBER token's text, as produced by the lexer or by `Value::literal`.
+    pub fn parse(text: &str) -> Option<Value> {
+        let (sign, magnitude) = match text.strip_prefix('-') {
+            Some(magnitude) => (-1.0, magnitude),
+            None => (1.0, text),
+        };
+        let special = match magnitude.strip_prefix("(double)").unwrap_or(magnitude) {
+            "INFINITY" | "inf" => Some(sign * f64::INFINITY),
+            "NAN" | "NaN" => Some(f64::NAN.copysign(sign)),
+            _ => None,
+        };
+        if let Some(special) = special {
+            // the lexer spells an overflowing double literal the way Rust does
+            return Some(match magnitude.starts_with("(double)") || magnitude == "inf" || magnitude == "NaN" {
+                true => Value::Double(special),
+                false => Value::Float(special as f32),
+            });
+        }
+        if text == INT_MIN {
+            return Some(Value::Int(i32::MIN));
+        }
+        if let Some(float) = text.strip_suffix('f') {
+            return float.parse().ok().map(Value::Float);
+        }
+        if text.contains(['.', 'e', 'E']) {
+            return text.parse().ok().map(Value::Double);
+        }
+        text.parse().ok().map(Value::Int)
+    }
+
+    /// Spells the value as a C literal of the same type.
+    pub fn literal(&self) -> String {
+        match self {
+            // C reads -2147483648 as the negation of a long
+            Value::Int(i32::MIN) => INT_MIN.to_string(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) if !value.is_finite() => Value::special(*value as f64, ""),
+            Value::Float(value) => format!("{:?}f", value),
+            Value::Double(value) if !value.is_finite() => Value::special(*value, "(double)"),
+            Value::Double(value) => Lexer::normalize_float(*value),
+        }
+    }
+
+    fn special(value: f64, cast: &str) -> String {
+        if value.is_nan() {
+            // printf shows the sign of a NaN, which x86 sets for inf - inf
+            let sign = if value.is_sign_negative() { "-" } else { "" };
+            format!("{sign}{cast}NAN")
+        } else if value > 0.0 {
+            format!("{cast}INFINITY")
+        } else {
+            format!("-{cast}INFINITY")
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Value::Int(value) => *value as f64,
+            Value::Float(value) => *value as f64,
+            Value::Double(value) => *value,
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Value::Int(_) => true,
+            Value::Float(value) => value.is_finite(),
+            Value::Double(value) => value.is_finite(),
+        }
+    }
+
+    pub fn negate(&self) -> Option<Value> {
+        match self {
+            Value::Int(value) => value.checked_neg().map(Value::Int),
+            Value::Float(value) => Some(Value::Float(-value)),
+            Value::Double(value) => Some(Value::Double(-value)),
+        }
+    }
+
+    /// Converts to the common type of `self` and `other`, like C's usual arithmetic conversions.
+    fn promote(self, other: Value) -> (Value, Value) {
+        match (self, other) {
+            (Value::Double(_), _) | (_, Value::Double(_)) => {
+                (Value::Double(self.as_f64()), Value::Double(other.as_f64()))
+            },
+            (Value::Float(_), _) | (_, Value::Float(_)) => {
+                (Value::Float(self.as_f64() as f32), Value::Float(other.as_f64() as f32))
+            },
+            _ => (self, other),
+        }
+    }
+
+    /// Applies an arithmetic operator, or returns None when the result is not a
+    /// well-defined finite value (integer overflow, division by zero, inf or NaN).
+    pub fn binary(op: TokenType, left: Value, right: Value) -> Option<Value> {
+        Value::checked(op, left, right).ok().filter(Value::is_finite)
+    }
+
+    /// Applies an arithmetic operator, telling why when finite operands do not
+    /// give a finite result, or when an `int` result overflows. Operands that
+    /// are already infinite or NaN simply propagate, as they would at run time.
+    pub fn checked(op: TokenType, left: Value, right: Value) -> Result<Value, Fault> {
+        if let (Value::Int(l), Value::Int(r)) = (left, right) {
+            let (l, r) = (i64::from(l), i64::from(r));
+            let exact = match op {
+                TokenType::SLASH if r == 0 => return Err(Fault::DivisionByZero),
+                TokenType::PLUS => l + r,
+                TokenType::MINUS => l - r,
+                TokenType::ASTERISK => l * r,
+                TokenType::SLASH => l / r,
+                _ => unreachable!("Not an arithmetic operator: {op}"),
+            };
+            if i32::try_from(exact).is_err() {
+                return Err(Fault::IntegerOverflow);
+            }
+        }
+        let result = Value::arithmetic(op, left, right)?;
+        if result.is_finite() || !left.is_finite() || !right.is_finite() {
+            Ok(result)
+        } else if op == TokenType::SLASH && right.as_f64() == 0.0 {
+            Err(Fault::DivisionByZero)
+        } else if result.as_f64().is_nan() {
+            Err(Fault::NotANumber)
+        } else {
+            Err(Fault::FloatOverflow)
+        }
+    }
+
+    /// Applies an arithmetic operator the way the C program does at run time:
+    /// floating-point results may be infinite or NaN, `int` results wrap
+    /// around, only integer division by zero fails.
+    pub fn arithmetic(op: TokenType, left: Value, right: Value) -> Result<Value, Fault> {
+        Ok(match left.promote(right) {
+            (Value::Int(l), Value::Int(r)) => Value::Int(match op {
+                TokenType::SLASH if r == 0 => return Err(Fault::DivisionByZero),
+                TokenType::PLUS => l.wrapping_add(r),
+                TokenType::MINUS => l.wrapping_sub(r),
+                TokenType::ASTERISK => l.wrapping_mul(r),
+                TokenType::SLASH => l.wrapping_div(r),
+                _ => unreachable!("Not an arithmetic operator: {op}"),
+            }),
+            (Value::Float(l), Value::Float(r)) => Value::Float(match op {
+                TokenType::PLUS => l + r,
+                TokenType::MINUS => l - r,
+                TokenType::ASTERISK => l * r,
+                TokenType::SLASH => l / r,
+                _ => unreachable!("Not an arithmetic operator: {op}"),
+            }),
+            (Value::Double(l), Value::Double(r)) => Value::Double(match op {
+                TokenType::PLUS => l + r,
+                TokenType::MINUS => l - r,
+                TokenType::ASTERISK => l * r,
+                TokenType::SLASH => l / r,
+                _ => unreachable!("Not an arithmetic operator: {op}"),
+            }),
+            _ => unreachable!("Operands should have been promoted to a common type"),
+        })
+    }
+
+    /// Applies a comparison operator (given by its text), giving a C `int` of 0 or 1.
+    pub fn compare(op: &str, left: Value, right: Value) -> Option<Value> {
+        let ordering = match left.promote(right) {
+            (Value::Int(l), Value::Int(r)) => l.partial_cmp(&r),
+            (Value::Float(l), Value::Float(r)) => l.partial_cmp(&r),
+            (Value::Double(l), Value::Double(r)) => l.partial_cmp(&r),
+            _ => unreachable!("Operands should have been promoted to a common type"),
+        };
+        let truth = match op {
+            "==" => ordering == Some(Ordering::Equal),
+            "!=" => ordering != Some(Ordering::Equal),
+            ">" => ordering == Some(Ordering::Greater),
+            ">=" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            "<" => ordering == Some(Ordering::Less),
+            "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            _ => return None,
+        };
+        Some(Value::Int(truth as i32))
+    }
+
+    /// Converts the value as assigning it to a BASIC (C `float`) variable would.
+    pub fn as_float(&self) -> Value {
+        Value::Float(self.as_f64() as f32)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.literal())
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{BufRead, Write};
+
+use crate::bytecode::{Chunk, Opcode};
+use crate::diagnostics::Diagnostic;
+use crate::lex::TokenType;
+use crate::runtime::Console;
+use crate::value::{Fault, Value};
+
+/*
+Stack virtual machine
+
+Runs a bytecode chunk (see `bytecode.rs`) with the semantics of the generated
+C, like the interpreter does for the AST. Slots start out as 0.
+*/
+
+pub struct Vm<R: BufRead, W: Write> {
+    console: Console<R, W>,
+    slots: Vec<Value>,
+    stack: Vec<Value>,
+}
+
+impl<R: BufRead, W: Write> Vm<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Vm { console: Console::new(input, output), slots: vec![], stack: vec![] }
+    }
+
+    pub fn run(&mut self, chunk: &Chunk) -> Result<(), Diagnostic> {
+        self.slots = vec![Value::Float(0.0); chunk.slots as usize];
+        self.stack.clear();
+
+        let mut pc = 0;
+        loop {
+            let (opcode, line) = chunk.code[pc];
+            pc += 1;
+            let fault = |fault: Fault| Diagnostic::error(line, fault.to_string());
+            match opcode {
+                Opcode::Constant(index) => self.stack.push(chunk.constants[index as usize]),
+                Opcode::Load(slot) => self.stack.push(self.slots[slot as usize]),
+                Opcode::Store(slot) => self.slots[slot as usize] = self.pop(line)?.as_float(),
+                Opcode::Set(slot) => self.slots[slot as usize] = self.pop(line)?,
+                Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div => {
+                    let right = self.pop(line)?;
+                    let left = self.pop(line)?;
+                    let op = match opcode {
+                        Opcode::Add => TokenType::PLUS,
+                        Opcode::Sub => TokenType::MINUS,
+                        Opcode::Mul => TokenType::ASTERISK,
+                        _ => TokenType::SLASH,
+                    };
+                    self.stack.push(Value::arithmetic(op, left, right).map_err(fault)?);
+                },
+                Opcode::Eq | Opcode::Ne | Opcode::Lt | Opcode::Le | Opcode::Gt | Opcode::Ge => {
+                    let right = self.pop(line)?;
+                    let left = self.pop(line)?;
+                    let op = match opcode {
+                        Opcode::Eq => "==",
+                        Opcode::Ne => "!=",
+                        Opcode::Lt => "<",
+                        Opcode::Le => "<=",
+                        Opcode::Gt => ">",
+                        _ => ">=",
+                    };
+                    self.stack.push(Value::compare(op, left, right).unwrap());
+                },
+                Opcode::Negate => {
+                    let value = self.pop(line)?;
+                    self.stack.push(value.negate().ok_or(fault(Fault::IntegerOverflow))?);
+                },
+                Opcode::Print => {
+                    let value = self.pop(line)?;
+                    self.console.print_value(value.as_f64() as f32);
+                },
+                Opcode::PrintString(index) => self.console.print_string(&chunk.strings[index as usize]),
+                Opcode::Input(slot) => {
+                    if let Some(value) = self.console.input() {
+                        self.slots[slot as usize] = Value::Float(value);
+                    }
+                },
+                Opcode::Jump(address) => pc = address as usize,
+                Opcode::JumpIfZero(address) => {
+                    if self.pop(line)?.as_f64() == 0.0 {
+                        pc = address as usize;
+                    }
+                },
+                Opcode::Halt => break,
+            }
+        }
+        self.console.flush();
+        Ok(())
+    }
+
+    fn pop(&mut self, line: u32) -> Result<Value, Diagnostic> {
+        self.stack.pop().ok_or_else(|| Diagnostic::error(line, "stack underflow in bytecode".to_string()))
+    }
+}
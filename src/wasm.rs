@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::ir::{Kind, Op, Operand, Program, Type};
+use crate::value::Value;
+
+/*
+WebAssembly text generation from the IR
+
+Writes a module in the text format (`prog.wat`) that runs in a sandbox with
+no access to anything but three imported host functions:
+
+    (import "host" "print" (func (param f32)))               printf("%.2f\n", x)
+    (import "host" "print_string" (func (param i32 i32)))    the line at offset, length in memory
+    (import "host" "input" (func (param f64) (result f64)))  scanf into x, or x at the end of input
+
+and exports the program as `main` along with its `memory`, which holds the
+strings. Variables are f64 locals holding float values (every store rounds to
+float first, as the C does), temporaries are locals of their C type, i32 for
+an int.
+
+WebAssembly only has structured control flow, so GOTO goes through a
+dispatcher. The code is cut into segments at each label and nested in blocks,
+innermost first, inside a loop:
+
+    (loop $dispatch
+      (block $s2
+        (block $s1
+          (block $s0
+            (br_table $s0 $s1 $s2 (local.get $label)))
+          segment 0)
+        segment 1)
+      segment 2)
+
+Breaking out of block $sN falls into segment N, and segments fall through to
+the next one as the labels in the IR do. A jump to the label starting segment
+N sets $label to N and branches back to $dispatch.
+*/
+
+pub fn emit(program: &Program) -> String {
+    let mut generator = Generator {
+        program,
+        segments: HashMap::new(),
+        data: vec![],
+        strings: vec![],
+    };
+    let mut segments = vec![vec![]];
+    for instruction in &program.instructions {
+        if let Kind::Label(label) = &instruction.kind {
+            generator.segments.insert(label.clone(), segments.len());
+            segments.push(vec![]);
+        }
+    }
+    let mut current = 0;
+    for instruction in &program.instructions {
+        match &instruction.kind {
+            Kind::Label(label) => current = generator.segments[label],
+            kind => {
+                let statement = generator.statement(kind);
+                segments[current].push(statement);
+            },
+        }
+    }
+    generator.finish(segments)
+}
+
+struct Generator<'a> {
+    program: &'a Program,
+    /// The segment each label starts.
+    segments: HashMap<String, usize>,
+    /// Memory contents, the strings back to back.
+    data: Vec<u8>,
+    /// Offset and length in `data` of each distinct string.
+    strings: Vec<(String, usize)>,
+}
+
+fn type_name(kind: Type) -> &'static str {
+    match kind {
+        Type::Int => "i32",
+        Type::Float => "f32",
+        Type::Double => "f64",
+    }
+}
+
+/// Spells a constant so the WAT reader gets back the exact same value.
+fn constant(value: Value) -> String {
+    fn float(text: String, nan: bool, negative: bool) -> String {
+        match (nan, negative) {
+            (true, false) => "nan".to_string(),
+            (true, true) => "-nan".to_string(),
+            _ => text,
+        }
+    }
+    match value {
+        Value::Int(value) => format!("(i32.const {})", value),
+        Value::Float(value) => {
+            format!("(f32.const {})", float(format!("{:?}", value), value.is_nan(), value.is_sign_negative()))
+        },
+        Value::Double(value) => {
+            format!("(f64.const {})", float(format!("{:?}", value), value.is_nan(), value.is_sign_negative()))
+        },
+    }
+}
+
+impl Generator<'_> {
+    fn string(&mut self, text: &str) -> (usize, usize) {
+        let offset = match self.strings.iter().find(|(string, _)| string == text) {
+            Some((_, offset)) => *offset,
+            None => {
+                let offset = self.data.len();
+                self.data.extend_from_slice(text.as_bytes());
+                self.strings.push((text.to_string(), offset));
+                offset
+            },
+        };
+        (offset, text.len())
+    }
+
+    /// An operand converted to `kind`, as a folded expression.
+    fn operand(&self, operand: &Operand, kind: Type) -> String {
+        let (expression, from) = match operand {
+            Operand::Constant(value) => {
+                return constant(match kind {
+                    Type::Int => *value,
+                    Type::Float => value.as_float(),
+                    Type::Double => Value::Double(value.as_f64()),
+                });
+            },
+            // the f64 local holds a float value, so widening it is free
+            Operand::Variable(name) => (format!("(local.get $v_{})", name), Type::Double),
+            Operand::Temp(temp) => (format!("(local.get $t{})", temp), self.program.temps[*temp]),
+        };
+        let conversion = match (from, kind) {
+            _ if from == kind => return expression,
+            (Type::Int, Type::Float) => "f32.convert_i32_s",
+            (Type::Int, Type::Double) => "f64.convert_i32_s",
+            (Type::Float, Type::Double) => "f64.promote_f32",
+            (Type::Double, Type::Float) => "f32.demote_f64",
+            _ => unreachable!("Cannot convert {:?} to {:?}", from, kind),
+        };
+        format!("({} {})", conversion, expression)
+    }
+
+    fn jump(&self, label: &str) -> String {
+        format!("(local.set $label (i32.const {})) (br $dispatch)", self.segments[label])
+    }
+
+    fn statement(&mut self, kind: &Kind) -> String {
+        match kind {
+            Kind::Binary { dest, op, left, right } => {
+                let operands = Type::common(self.program.operand_type(left), self.program.operand_type(right));
+                let left = self.operand(left, operands);
+                let right = self.operand(right, operands);
+                let name = type_name(operands);
+                let instruction = match (op, operands) {
+                    (Op::Add, _) => "add",
+                    (Op::Sub, _) => "sub",
+                    (Op::Mul, _) => "mul",
+                    (Op::Div, Type::Int) => "div_s",
+                    (Op::Div, _) => "div",
+                    (Op::Eq, _) => "eq",
+                    (Op::Ne, _) => "ne",
+                    (Op::Lt, Type::Int) => "lt_s",
+                    (Op::Le, Type::Int) => "le_s",
+                    (Op::Gt, Type::Int) => "gt_s",
+                    (Op::Ge, Type::Int) => "ge_s",
+                    (Op::Lt, _) => "lt",
+                    (Op::Le, _) => "le",
+                    (Op::Gt, _) => "gt",
+                    (Op::Ge, _) => "ge",
+                };
+                // comparisons give an i32 0 or 1, as they do in C
+                format!("(local.set $t{} ({}.{} {} {}))", dest, name, instruction, left, right)
+            },
+            Kind::Negate { dest, operand } => {
+                let kind = self.program.temps[*dest];
+                let operand = self.operand(operand, kind);
+                match kind {
+                    Type::Int => format!("(local.set $t{} (i32.sub (i32.const 0) {}))", dest, operand),
+                    _ => format!("(local.set $t{} ({}.neg {}))", dest, type_name(kind), operand),
+                }
+            },
+            Kind::Store { variable, value } => {
+                let value = self.operand(value, Type::Float);
+                format!("(local.set $v_{} (f64.promote_f32 {}))", variable, value)
+            },
+            Kind::Input { variable } => {
+                format!("(local.set $v_{0} (call $input (local.get $v_{0})))", variable)
+            },
+            Kind::PrintString(text) => {
+                let (offset, length) = self.string(text);
+                format!("(call $print_string (i32.const {}) (i32.const {}))", offset, length)
+            },
+            Kind::PrintValue(value) => format!("(call $print {})", self.operand(value, Type::Float)),
+            Kind::Jump(label) => self.jump(label),
+            Kind::JumpIfZero { condition, target } => {
+                // NaN is not zero, and `eq` is false for it
+                let test = match self.program.operand_type(condition) {
+                    Type::Int => format!("(i32.eqz {})", self.operand(condition, Type::Int)),
+                    Type::Float => format!("(f32.eq {} (f32.const 0))", self.operand(condition, Type::Float)),
+                    Type::Double => format!("(f64.eq {} (f64.const 0))", self.operand(condition, Type::Double)),
+                };
+                format!("(if {} (then {}))", test, self.jump(target))
+            },
+            Kind::Label(_) => unreachable!("Labels start segments"),
+        }
+    }
+
+    fn finish(self, segments: Vec<Vec<String>>) -> String {
+        let mut output = String::from("(module\n");
+        output += "  (import \"host\" \"print\" (func $print (param f32)))\n";
+        output += "  (import \"host\" \"print_string\" (func $print_string (param i32 i32)))\n";
+        output += "  (import \"host\" \"input\" (func $input (param f64) (result f64)))\n";
+        output += &format!("  (memory (export \"memory\") {})\n", self.data.len().div_ceil(65536).max(1));
+        if !self.data.is_empty() {
+            let mut bytes = String::new();
+            for byte in &self.data {
+                if byte.is_ascii_graphic() && *byte != b'"' && *byte != b'\\' || *byte == b' ' {
+                    bytes.push(*byte as char);
+                } else {
+                    bytes += &format!("\\{:02x}", byte);
+                }
+            }
+            output += &format!("  (data (i32.const 0) \"{}\")\n", bytes);
+        }
+
+        output += "  (func $main (export \"main\")\n";
+        for variable in self.program.variables() {
+            output += &format!("    (local $v_{} f64)\n", variable);
+        }
+        for (temp, kind) in self.program.temps.iter().enumerate() {
+            output += &format!("    (local $t{} {})\n", temp, type_name(*kind));
+        }
+        output += "    (local $label i32)\n";
+
+        let count = segments.len();
+        let indent = |depth: usize| " ".repeat(2 * depth + 4);
+        output += &format!("{}(loop $dispatch\n", indent(0));
+        for segment in (0..count).rev() {
+            output += &format!("{}(block $s{}\n", indent(count - segment), segment);
+        }
+        let targets: Vec<String> = (0..count).map(|segment| format!("$s{}", segment)).collect();
+        output += &format!("{}(br_table {} (local.get $label)))\n", indent(count + 1), targets.join(" "));
+        for (segment, statements) in segments.iter().enumerate() {
+            for statement in statements {
+                output += &format!("{}{}\n", indent(count - segment), statement);
+            }
+            output.pop();
+            output += ")\n";
+        }
+        output += "  )\n)\n";
+        output
+    }
+}
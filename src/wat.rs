@@ -0,0 +1,690 @@
+use std::io::{BufRead, Write};
+
+use crate::runtime::Console;
+
+/*
+WebAssembly text runner
+
+Validates and runs the modules `wasm.rs` writes, so they can be tested without
+a WebAssembly runtime. It reads the text format in its folded form only, and
+only the subset the backend uses: the three "host" imports, one memory with a
+data segment, and an exported `main` function without parameters or results
+whose body uses locals, constants, numeric instructions, calls, block, loop,
+if and br/br_table. Blocks have no results.
+
+Loading checks what a WebAssembly validator would for that subset: every
+name resolves, every instruction gets operands of the right types and
+statements leave nothing on the stack. Running traps like WebAssembly does on
+integer division by zero or overflow and on memory accesses out of bounds.
+The host functions use the same console as the interpreter and the VM.
+*/
+
+#[derive(Clone, Debug)]
+enum Sexpr {
+    List(Vec<Sexpr>),
+    Atom(String),
+    Str(Vec<u8>),
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum ValType {
+    I32,
+    F32,
+    F64,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Val {
+    I32(i32),
+    F32(f32),
+    F64(f64),
+}
+
+impl Val {
+    fn zero(kind: ValType) -> Val {
+        match kind {
+            ValType::I32 => Val::I32(0),
+            ValType::F32 => Val::F32(0.0),
+            ValType::F64 => Val::F64(0.0),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Import {
+    Print,
+    PrintString,
+}
+
+enum Expr {
+    Const(Val),
+    Get(usize),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    /// Only `input` returns a value.
+    Input(Box<Expr>),
+}
+
+enum Stmt {
+    Set(usize, Expr),
+    Call(Import, Vec<Expr>),
+    Block(Vec<Stmt>),
+    Loop(Vec<Stmt>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    /// Branches by depth, 0 being the innermost enclosing block or loop.
+    Br(usize),
+    BrTable(Vec<usize>, usize, Expr),
+    Return,
+}
+
+pub struct Module {
+    memory: Vec<u8>,
+    locals: Vec<ValType>,
+    body: Vec<Stmt>,
+}
+
+/// Operand and result types of the numeric instructions.
+const OPERATORS: &[(&str, &[ValType], ValType)] = {
+    use ValType::*;
+    &[
+        ("i32.eqz", &[I32], I32),
+        ("f32.convert_i32_s", &[I32], F32),
+        ("f64.convert_i32_s", &[I32], F64),
+        ("f64.promote_f32", &[F32], F64),
+        ("f32.demote_f64", &[F64], F32),
+        ("f32.neg", &[F32], F32),
+        ("f64.neg", &[F64], F64),
+        ("i32.add", &[I32, I32], I32),
+        ("i32.sub", &[I32, I32], I32),
+        ("i32.mul", &[I32, I32], I32),
+        ("i32.div_s", &[I32, I32], I32),
+        ("i32.eq", &[I32, I32], I32),
+        ("i32.ne", &[I32, I32], I32),
+        ("i32.lt_s", &[I32, I32], I32),
+        ("i32.le_s", &[I32, I32], I32),
+        ("i32.gt_s", &[I32, I32], I32),
+        ("i32.ge_s", &[I32, I32], I32),
+        ("f32.add", &[F32, F32], F32),
+        ("f32.sub", &[F32, F32], F32),
+        ("f32.mul", &[F32, F32], F32),
+        ("f32.div", &[F32, F32], F32),
+        ("f32.eq", &[F32, F32], I32),
+        ("f32.ne", &[F32, F32], I32),
+        ("f32.lt", &[F32, F32], I32),
+        ("f32.le", &[F32, F32], I32),
+        ("f32.gt", &[F32, F32], I32),
+        ("f32.ge", &[F32, F32], I32),
+        ("f64.add", &[F64, F64], F64),
+        ("f64.sub", &[F64, F64], F64),
+        ("f64.mul", &[F64, F64], F64),
+        ("f64.div", &[F64, F64], F64),
+        ("f64.eq", &[F64, F64], I32),
+        ("f64.ne", &[F64, F64], I32),
+        ("f64.lt", &[F64, F64], I32),
+        ("f64.le", &[F64, F64], I32),
+        ("f64.gt", &[F64, F64], I32),
+        ("f64.ge", &[F64, F64], I32),
+    ]
+};
+
+fn tokenize(text: &str) -> Result<Vec<Sexpr>, String> {
+    let bytes = text.as_bytes();
+    let mut stack: Vec<Vec<Sexpr>> = vec![vec![]];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => i += 1,
+            b';' if bytes.get(i + 1) == Some(&b';') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            },
+            b'(' if bytes.get(i + 1) == Some(&b';') => {
+                let end = text[i..].find(";)").ok_or("unterminated block comment")?;
+                i += end + 2;
+            },
+            b'(' => {
+                stack.push(vec![]);
+                i += 1;
+            },
+            b')' => {
+                let list = stack.pop().filter(|_| !stack.is_empty()).ok_or("unbalanced ')'")?;
+                stack.last_mut().unwrap().push(Sexpr::List(list));
+                i += 1;
+            },
+            b'"' => {
+                let mut string = vec![];
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => return Err("unterminated string".to_string()),
+                        Some(b'"') => break,
+                        Some(b'\\') => {
+                            let escape = match bytes.get(i + 1) {
+                                Some(b'n') => b'\n',
+                                Some(b't') => b'\t',
+                                Some(b'\\') => b'\\',
+                                Some(b'"') => b'"',
+                                Some(b'\'') => b'\'',
+                                _ => {
+                                    let hex = text.get(i + 1..i + 3).ok_or("bad string escape")?;
+                                    i += 1;
+                                    u8::from_str_radix(hex, 16).map_err(|_| format!("bad string escape \\{hex}"))?
+                                },
+                            };
+                            string.push(escape);
+                            i += 2;
+                        },
+                        Some(byte) => {
+                            string.push(*byte);
+                            i += 1;
+                        },
+                    }
+                }
+                stack.last_mut().unwrap().push(Sexpr::Str(string));
+                i += 1;
+            },
+            _ => {
+                let start = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"()\";".contains(&bytes[i]) {
+                    i += 1;
+                }
+                stack.last_mut().unwrap().push(Sexpr::Atom(text[start..i].to_string()));
+            },
+        }
+    }
+    match stack.pop() {
+        Some(top) if stack.is_empty() => Ok(top),
+        _ => Err("missing ')'".to_string()),
+    }
+}
+
+fn atom(sexpr: Option<&Sexpr>) -> Option<&str> {
+    match sexpr {
+        Some(Sexpr::Atom(text)) => Some(text),
+        _ => None,
+    }
+}
+
+fn list(sexpr: &Sexpr) -> Result<&[Sexpr], String> {
+    match sexpr {
+        Sexpr::List(items) => Ok(items),
+        other => Err(format!("expected a folded instruction, got {other:?}")),
+    }
+}
+
+fn value_type(name: Option<&str>) -> Result<ValType, String> {
+    match name {
+        Some("i32") => Ok(ValType::I32),
+        Some("f32") => Ok(ValType::F32),
+        Some("f64") => Ok(ValType::F64),
+        other => Err(format!("unknown value type {other:?}")),
+    }
+}
+
+fn parse_float(text: &str) -> Option<f64> {
+    let (negative, magnitude) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let value = match magnitude {
+        "inf" => f64::INFINITY,
+        "nan" => f64::NAN,
+        _ if magnitude.starts_with(|c: char| c.is_ascii_digit()) => magnitude.replace('_', "").parse().ok()?,
+        _ => return None,
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_f32(text: &str) -> Option<f32> {
+    let value = parse_float(text)?;
+    // parse decimals straight to f32 rather than rounding twice through f64
+    let magnitude = text.trim_start_matches(['+', '-']);
+    if value.is_finite() {
+        let single: f32 = magnitude.replace('_', "").parse().ok()?;
+        return Some(if text.starts_with('-') { -single } else { single });
+    }
+    Some(value as f32)
+}
+
+/// Names in scope while loading the function body.
+struct Scope {
+    locals: Vec<(String, ValType)>,
+    labels: Vec<Option<String>>,
+}
+
+impl Scope {
+    fn local(&self, name: Option<&str>) -> Result<(usize, ValType), String> {
+        let name = name.ok_or("missing local name")?;
+        if let Ok(index) = name.parse::<usize>() {
+            return self.locals.get(index).map(|(_, kind)| (index, *kind)).ok_or(format!("unknown local {name}"));
+        }
+        self.locals.iter().position(|(local, _)| local == name)
+            .map(|index| (index, self.locals[index].1))
+            .ok_or(format!("unknown local {name}"))
+    }
+
+    fn label(&self, name: Option<&str>) -> Result<usize, String> {
+        let name = name.ok_or("missing label")?;
+        if let Ok(depth) = name.parse::<usize>() {
+            return if depth < self.labels.len() { Ok(depth) } else { Err(format!("unknown label {name}")) };
+        }
+        self.labels.iter().rev().position(|label| label.as_deref() == Some(name)).ok_or(format!("unknown label {name}"))
+    }
+
+    fn expression(&self, sexpr: &Sexpr) -> Result<(Expr, ValType), String> {
+        let items = list(sexpr)?;
+        let head = atom(items.first()).ok_or("expected an instruction")?;
+        let operands = &items[1..];
+        let expect = |count: usize| {
+            if operands.len() == count {
+                Ok(())
+            } else {
+                Err(format!("{head} takes {count} operands, got {}", operands.len()))
+            }
+        };
+        match head {
+            "i32.const" | "f32.const" | "f64.const" => {
+                expect(1)?;
+                let text = atom(operands.first()).ok_or(format!("{head} needs a literal"))?;
+                let bad = || format!("bad literal {text} for {head}");
+                let value = match head {
+                    "i32.const" => Val::I32(text.replace('_', "").parse().map_err(|_| bad())?),
+                    "f32.const" => Val::F32(parse_f32(text).ok_or_else(bad)?),
+                    _ => Val::F64(parse_float(text).ok_or_else(bad)?),
+                };
+                let kind = value_type(head.split('.').next())?;
+                Ok((Expr::Const(value), kind))
+            },
+            "local.get" => {
+                let (index, kind) = self.local(atom(operands.first()))?;
+                Ok((Expr::Get(index), kind))
+            },
+            "call" if atom(operands.first()) == Some("$input") => {
+                expect(2)?;
+                let argument = self.typed(&operands[1], ValType::F64)?;
+                Ok((Expr::Input(Box::new(argument)), ValType::F64))
+            },
+            _ => {
+                let (name, types, result) = OPERATORS.iter().find(|(name, _, _)| *name == head)
+                    .ok_or(format!("unsupported instruction {head}"))?;
+                expect(types.len())?;
+                let mut arguments = vec![];
+                for (operand, kind) in operands.iter().zip(types.iter()) {
+                    arguments.push(self.typed(operand, *kind)?);
+                }
+                let expression = match arguments.len() {
+                    1 => Expr::Unary(name, Box::new(arguments.remove(0))),
+                    _ => Expr::Binary(name, Box::new(arguments.remove(0)), Box::new(arguments.remove(0))),
+                };
+                Ok((expression, *result))
+            },
+        }
+    }
+
+    fn typed(&self, sexpr: &Sexpr, kind: ValType) -> Result<Expr, String> {
+        let (expression, actual) = self.expression(sexpr)?;
+        if actual != kind {
+            let (expected, actual) = (format!("{kind:?}"), format!("{actual:?}"));
+            return Err(format!("type mismatch: expected {}, got {}", expected.to_lowercase(), actual.to_lowercase()));
+        }
+        Ok(expression)
+    }
+
+    fn statements(&mut self, sexprs: &[Sexpr]) -> Result<Vec<Stmt>, String> {
+        sexprs.iter().map(|sexpr| self.statement(sexpr)).collect()
+    }
+
+    fn statement(&mut self, sexpr: &Sexpr) -> Result<Stmt, String> {
+        let items = list(sexpr)?;
+        let head = atom(items.first()).ok_or("expected an instruction")?;
+        match head {
+            "local.set" => {
+                if items.len() != 3 {
+                    return Err("local.set takes a local and a value".to_string());
+                }
+                let (index, kind) = self.local(atom(items.get(1)))?;
+                Ok(Stmt::Set(index, self.typed(&items[2], kind)?))
+            },
+            "call" => {
+                let (import, types): (Import, &[ValType]) = match atom(items.get(1)) {
+                    Some("$print") => (Import::Print, &[ValType::F32]),
+                    Some("$print_string") => (Import::PrintString, &[ValType::I32, ValType::I32]),
+                    Some("$input") => return Err("the result of $input is dropped".to_string()),
+                    other => return Err(format!("unknown function {other:?}")),
+                };
+                if items.len() != types.len() + 2 {
+                    return Err(format!("wrong number of arguments to {}", atom(items.get(1)).unwrap()));
+                }
+                let mut arguments = vec![];
+                for (operand, kind) in items[2..].iter().zip(types.iter()) {
+                    arguments.push(self.typed(operand, *kind)?);
+                }
+                Ok(Stmt::Call(import, arguments))
+            },
+            "block" | "loop" => {
+                let (label, body) = match atom(items.get(1)) {
+                    Some(name) if name.starts_with('$') => (Some(name.to_string()), &items[2..]),
+                    _ => (None, &items[1..]),
+                };
+                self.labels.push(label);
+                let body = self.statements(body);
+                self.labels.pop();
+                Ok(if head == "block" { Stmt::Block(body?) } else { Stmt::Loop(body?) })
+            },
+            "if" => {
+                let condition = self.typed(items.get(1).ok_or("if needs a condition")?, ValType::I32)?;
+                let mut branches = [vec![], vec![]];
+                self.labels.push(None);
+                for item in &items[2..] {
+                    let parts = list(item)?;
+                    let branch = match atom(parts.first()) {
+                        Some("then") => 0,
+                        Some("else") => 1,
+                        _ => return Err("if takes (then ...) and (else ...)".to_string()),
+                    };
+                    branches[branch] = self.statements(&parts[1..])?;
+                }
+                self.labels.pop();
+                let [then, otherwise] = branches;
+                Ok(Stmt::If(condition, then, otherwise))
+            },
+            "br" => Ok(Stmt::Br(self.label(atom(items.get(1)))?)),
+            "br_table" => {
+                let index = self.typed(items.last().ok_or("br_table needs an index")?, ValType::I32)?;
+                let mut targets = items[1..items.len() - 1].iter()
+                    .map(|item| self.label(atom(Some(item))))
+                    .collect::<Result<Vec<usize>, String>>()?;
+                let default = targets.pop().ok_or("br_table needs a default label")?;
+                Ok(Stmt::BrTable(targets, default, index))
+            },
+            "return" => Ok(Stmt::Return),
+            _ => Err(format!("{head} leaves a value on the stack or is unsupported")),
+        }
+    }
+}
+
+impl Module {
+    pub fn load(text: &str) -> Result<Module, String> {
+        let top = tokenize(text)?;
+        let [Sexpr::List(module)] = top.as_slice() else {
+            return Err("expected a single (module ...)".to_string());
+        };
+        if atom(module.first()) != Some("module") {
+            return Err("expected a single (module ...)".to_string());
+        }
+
+        let mut memory = None;
+        let mut data = vec![];
+        let mut main = None;
+        for field in &module[1..] {
+            let items = list(field)?;
+            match atom(items.first()) {
+                Some("import") => {
+                    let expected = match (&items.get(1), &items.get(2)) {
+                        (Some(Sexpr::Str(module)), Some(Sexpr::Str(name))) if module == b"host" => {
+                            match name.as_slice() {
+                                b"print" => "(func $print (param f32))",
+                                b"print_string" => "(func $print_string (param i32 i32))",
+                                b"input" => "(func $input (param f64) (result f64))",
+                                _ => return Err(format!("unknown import {}", String::from_utf8_lossy(name))),
+                            }
+                        },
+                        _ => return Err("imports must come from \"host\"".to_string()),
+                    };
+                    let actual = items.get(3).map(|item| format!("{}", Written(item)));
+                    if actual.as_deref() != Some(expected) {
+                        return Err(format!("import should be {expected}"));
+                    }
+                },
+                Some("memory") => {
+                    let pages = atom(items.last()).and_then(|pages| pages.parse::<usize>().ok())
+                        .ok_or("memory needs a size in pages")?;
+                    memory = Some(vec![0; pages * 65536]);
+                },
+                Some("data") => {
+                    let offset = match items.get(1).map(list) {
+                        Some(Ok([Sexpr::Atom(op), Sexpr::Atom(offset)])) if op == "i32.const" => {
+                            offset.parse::<usize>().map_err(|_| "bad data offset")?
+                        },
+                        _ => return Err("data needs an (i32.const offset)".to_string()),
+                    };
+                    let mut bytes = vec![];
+                    for item in &items[2..] {
+                        let Sexpr::Str(string) = item else {
+                            return Err("data holds strings".to_string());
+                        };
+                        bytes.extend_from_slice(string);
+                    }
+                    data.push((offset, bytes));
+                },
+                Some("func") => {
+                    if main.is_some() {
+                        return Err("only one function is supported".to_string());
+                    }
+                    main = Some(items);
+                },
+                other => return Err(format!("unsupported module field {other:?}")),
+            }
+        }
+
+        let mut memory = memory.unwrap_or_default();
+        for (offset, bytes) in data {
+            memory.get_mut(offset..offset + bytes.len()).ok_or("data segment does not fit in memory")?
+                .copy_from_slice(&bytes);
+        }
+
+        let function = main.ok_or("no function")?;
+        let mut scope = Scope { locals: vec![], labels: vec![] };
+        let mut exported = false;
+        let mut body = &function[1..];
+        while let Some(item) = body.first() {
+            match item {
+                Sexpr::Atom(name) if name.starts_with('$') => {},
+                Sexpr::List(parts) if atom(parts.first()) == Some("export") => {
+                    exported = matches!(parts.get(1), Some(Sexpr::Str(name)) if name == b"main");
+                },
+                Sexpr::List(parts) if atom(parts.first()) == Some("local") => {
+                    match atom(parts.get(1)) {
+                        Some(name) if name.starts_with('$') => {
+                            scope.locals.push((name.to_string(), value_type(atom(parts.get(2)))?));
+                        },
+                        _ => {
+                            for part in &parts[1..] {
+                                scope.locals.push((String::new(), value_type(atom(Some(part)))?));
+                            }
+                        },
+                    }
+                },
+                Sexpr::List(parts) if matches!(atom(parts.first()), Some("param" | "result")) => {
+                    return Err("main takes no parameters and returns nothing".to_string());
+                },
+                _ => break,
+            }
+            body = &body[1..];
+        }
+        if !exported {
+            return Err("the function must be exported as \"main\"".to_string());
+        }
+        scope.labels.push(None);
+        let body = scope.statements(body)?;
+        let locals = scope.locals.iter().map(|(_, kind)| *kind).collect();
+        Ok(Module { memory, locals, body })
+    }
+}
+
+/// Writes an S-expression back out, to compare import signatures.
+struct Written<'a>(&'a Sexpr);
+
+impl std::fmt::Display for Written<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Sexpr::Atom(text) => write!(f, "{text}"),
+            Sexpr::Str(bytes) => write!(f, "\"{}\"", String::from_utf8_lossy(bytes)),
+            Sexpr::List(items) => {
+                let items: Vec<String> = items.iter().map(|item| Written(item).to_string()).collect();
+                write!(f, "({})", items.join(" "))
+            },
+        }
+    }
+}
+
+enum Flow {
+    Next,
+    Branch(usize),
+    Return,
+}
+
+pub struct Runner<R: BufRead, W: Write> {
+    console: Console<R, W>,
+    locals: Vec<Val>,
+}
+
+impl<R: BufRead, W: Write> Runner<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Runner { console: Console::new(input, output), locals: vec![] }
+    }
+
+    /// Runs `main`, or returns the message of the trap that stopped it.
+    pub fn run(&mut self, module: &Module) -> Result<(), String> {
+        self.locals = module.locals.iter().map(|kind| Val::zero(*kind)).collect();
+        let result = self.block(&module.body, &module.memory);
+        self.console.flush();
+        result.map(|_| ())
+    }
+
+    fn block(&mut self, statements: &[Stmt], memory: &[u8]) -> Result<Flow, String> {
+        for statement in statements {
+            match self.statement(statement, memory)? {
+                Flow::Next => {},
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Leaves a block or loop: a branch to it ends here, outer ones carry on.
+    fn leave(flow: Flow) -> Flow {
+        match flow {
+            Flow::Branch(0) => Flow::Next,
+            Flow::Branch(depth) => Flow::Branch(depth - 1),
+            flow => flow,
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt, memory: &[u8]) -> Result<Flow, String> {
+        match statement {
+            Stmt::Set(index, value) => self.locals[*index] = self.expression(value)?,
+            Stmt::Call(import, arguments) => {
+                let arguments = arguments.iter().map(|argument| self.expression(argument))
+                    .collect::<Result<Vec<Val>, String>>()?;
+                match (import, arguments.as_slice()) {
+                    (Import::Print, [Val::F32(value)]) => self.console.print_value(*value),
+                    (Import::PrintString, [Val::I32(offset), Val::I32(length)]) => {
+                        let start = *offset as u32 as usize;
+                        let bytes = memory.get(start..start + *length as u32 as usize)
+                            .ok_or("out of bounds memory access")?;
+                        self.console.print_string(&String::from_utf8_lossy(bytes));
+                    },
+                    _ => unreachable!("Calls are checked when loading"),
+                }
+            },
+            Stmt::Block(body) => return Ok(Runner::<R, W>::leave(self.block(body, memory)?)),
+            Stmt::Loop(body) => loop {
+                match self.block(body, memory)? {
+                    Flow::Branch(0) => continue,
+                    flow => return Ok(Runner::<R, W>::leave(flow)),
+                }
+            },
+            Stmt::If(condition, then, otherwise) => {
+                let Val::I32(condition) = self.expression(condition)? else { unreachable!() };
+                let body = if condition != 0 { then } else { otherwise };
+                return Ok(Runner::<R, W>::leave(self.block(body, memory)?));
+            },
+            Stmt::Br(depth) => return Ok(Flow::Branch(*depth)),
+            Stmt::BrTable(targets, default, index) => {
+                let Val::I32(index) = self.expression(index)? else { unreachable!() };
+                return Ok(Flow::Branch(*targets.get(index as u32 as usize).unwrap_or(default)));
+            },
+            Stmt::Return => return Ok(Flow::Return),
+        }
+        Ok(Flow::Next)
+    }
+
+    fn expression(&mut self, expression: &Expr) -> Result<Val, String> {
+        Ok(match expression {
+            Expr::Const(value) => *value,
+            Expr::Get(index) => self.locals[*index],
+            Expr::Input(current) => {
+                let Val::F64(current) = self.expression(current)? else { unreachable!() };
+                Val::F64(self.console.input().map_or(current, |value| value as f64))
+            },
+            Expr::Unary(name, operand) => {
+                let operand = self.expression(operand)?;
+                unary(name, operand)
+            },
+            Expr::Binary(name, left, right) => {
+                let left = self.expression(left)?;
+                let right = self.expression(right)?;
+                binary(name, left, right)?
+            },
+        })
+    }
+}
+
+fn unary(name: &str, operand: Val) -> Val {
+    match (name, operand) {
+        ("i32.eqz", Val::I32(value)) => Val::I32((value == 0) as i32),
+        ("f32.convert_i32_s", Val::I32(value)) => Val::F32(value as f32),
+        ("f64.convert_i32_s", Val::I32(value)) => Val::F64(value as f64),
+        ("f64.promote_f32", Val::F32(value)) => Val::F64(value as f64),
+        ("f32.demote_f64", Val::F64(value)) => Val::F32(value as f32),
+        ("f32.neg", Val::F32(value)) => Val::F32(-value),
+        ("f64.neg", Val::F64(value)) => Val::F64(-value),
+        _ => unreachable!("Operand types are checked when loading"),
+    }
+}
+
+fn binary(name: &str, left: Val, right: Val) -> Result<Val, String> {
+    let (kind, op) = name.split_once('.').unwrap();
+    Ok(match (left, right) {
+        (Val::I32(l), Val::I32(r)) => match op {
+            "add" => Val::I32(l.wrapping_add(r)),
+            "sub" => Val::I32(l.wrapping_sub(r)),
+            "mul" => Val::I32(l.wrapping_mul(r)),
+            "div_s" if r == 0 => return Err("integer divide by zero".to_string()),
+            "div_s" => Val::I32(l.checked_div(r).ok_or("integer overflow")?),
+            _ => Val::I32(compare(op, l.partial_cmp(&r))),
+        },
+        (Val::F32(l), Val::F32(r)) => match op {
+            "add" => Val::F32(l + r),
+            "sub" => Val::F32(l - r),
+            "mul" => Val::F32(l * r),
+            "div" => Val::F32(l / r),
+            _ => Val::I32(compare(op, l.partial_cmp(&r))),
+        },
+        (Val::F64(l), Val::F64(r)) => match op {
+            "add" => Val::F64(l + r),
+            "sub" => Val::F64(l - r),
+            "mul" => Val::F64(l * r),
+            "div" => Val::F64(l / r),
+            _ => Val::I32(compare(op, l.partial_cmp(&r))),
+        },
+        _ => unreachable!("Operand types of {kind}.{op} are checked when loading"),
+    })
+}
+
+/// Comparisons are false when unordered (NaN), except `ne`.
+fn compare(op: &str, ordering: Option<std::cmp::Ordering>) -> i32 {
+    use std::cmp::Ordering::*;
+    let truth = match op {
+        "eq" => ordering == Some(Equal),
+        "ne" => ordering != Some(Equal),
+        "lt" | "lt_s" => ordering == Some(Less),
+        "le" | "le_s" => matches!(ordering, Some(Less | Equal)),
+        "gt" | "gt_s" => ordering == Some(Greater),
+        "ge" | "ge_s" => matches!(ordering, Some(Greater | Equal)),
+        _ => unreachable!("Unknown comparison {op}"),
+    };
+    truth as i32
+}
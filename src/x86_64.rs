@@ -0,0 +1,309 @@
+use crate::ir::{Kind, Op, Operand, Program, Type};
+use crate::value::Value;
+
+/*
+x86-64 assembly generation from the IR
+
+Writes GNU assembler (AT&T syntax) for Linux that `cc prog.s` assembles and
+links against the C library. It computes what the generated C would: floats
+and doubles use the scalar SSE instructions, C `int`s the 32-bit forms of
+the general purpose registers, with the same conversions. PRINT and INPUT
+call printf and scanf following the System V ABI.
+
+Variables live in .data as `v_<name>`, temporaries in 8-byte stack slots and
+constants in .rodata. Labels become `.L_<label>`, local to the file. Every
+instruction loads its operands into %eax/%ecx or %xmm0/%xmm1 and stores the
+result back, so no register allocation is needed.
+*/
+
+pub fn emit(program: &Program) -> String {
+    let mut generator = Generator {
+        program,
+        text: String::new(),
+        constants: vec![],
+        strings: vec![],
+        next_local: 0,
+    };
+    for instruction in &program.instructions {
+        generator.instruction(&instruction.kind);
+    }
+    generator.finish()
+}
+
+struct Generator<'a> {
+    program: &'a Program,
+    text: String,
+    /// Constants in .rodata, told apart by their C spelling.
+    constants: Vec<Value>,
+    strings: Vec<String>,
+    next_local: usize,
+}
+
+impl Generator<'_> {
+    fn line(&mut self, line: &str) {
+        self.text += "    ";
+        self.text += line;
+        self.text += "\n";
+    }
+
+    fn local_label(&mut self) -> String {
+        self.next_local += 1;
+        format!(".Lx{}", self.next_local - 1)
+    }
+
+    fn slot(temp: usize) -> String {
+        format!("-{}(%rbp)", 8 * (temp + 1))
+    }
+
+    fn constant(&mut self, value: Value) -> String {
+        let index = match self.constants.iter().position(|constant| constant.literal() == value.literal()) {
+            Some(index) => index,
+            None => {
+                self.constants.push(value);
+                self.constants.len() - 1
+            },
+        };
+        format!(".LC{}(%rip)", index)
+    }
+
+    fn string(&mut self, text: &str) -> String {
+        let index = match self.strings.iter().position(|string| string == text) {
+            Some(index) => index,
+            None => {
+                self.strings.push(text.to_string());
+                self.strings.len() - 1
+            },
+        };
+        format!(".LS{}(%rip)", index)
+    }
+
+    /// Loads an operand converted to `kind`: an Int into %eax (or %ecx as the
+    /// second operand), a Float or Double into %xmm0 (or %xmm1).
+    fn load(&mut self, operand: &Operand, kind: Type, second: bool) {
+        let (register, xmm) = if second { ("%ecx", "%xmm1") } else { ("%eax", "%xmm0") };
+        let from = self.program.operand_type(operand);
+        if let Operand::Constant(value) = operand {
+            let converted = match kind {
+                Type::Int => *value,
+                Type::Float => value.as_float(),
+                Type::Double => Value::Double(value.as_f64()),
+            };
+            let instruction = match converted {
+                Value::Int(value) => format!("movl ${}, {}", value, register),
+                Value::Float(_) => format!("movss {}, {}", self.constant(converted), xmm),
+                Value::Double(_) => format!("movsd {}, {}", self.constant(converted), xmm),
+            };
+            return self.line(&instruction);
+        }
+        let location = match operand {
+            Operand::Variable(name) => format!("v_{}(%rip)", name),
+            Operand::Temp(temp) => Generator::slot(*temp),
+            Operand::Constant(_) => unreachable!(),
+        };
+        let instruction = match (from, kind) {
+            (Type::Int, Type::Int) => format!("movl {}, {}", location, register),
+            (Type::Int, Type::Float) => format!("cvtsi2ssl {}, {}", location, xmm),
+            (Type::Int, Type::Double) => format!("cvtsi2sdl {}, {}", location, xmm),
+            (Type::Float, Type::Float) => format!("movss {}, {}", location, xmm),
+            (Type::Float, Type::Double) => format!("cvtss2sd {}, {}", location, xmm),
+            (Type::Double, Type::Double) => format!("movsd {}, {}", location, xmm),
+            (Type::Double, Type::Float) => format!("cvtsd2ss {}, {}", location, xmm),
+            _ => unreachable!("Cannot convert {:?} to {:?}", from, kind),
+        };
+        self.line(&instruction);
+    }
+
+    fn store(&mut self, temp: usize) {
+        let instruction = match self.program.temps[temp] {
+            Type::Int => format!("movl %eax, {}", Generator::slot(temp)),
+            Type::Float => format!("movss %xmm0, {}", Generator::slot(temp)),
+            Type::Double => format!("movsd %xmm0, {}", Generator::slot(temp)),
+        };
+        self.line(&instruction);
+    }
+
+    fn instruction(&mut self, kind: &Kind) {
+        match kind {
+            Kind::Binary { dest, op, left, right } => {
+                let operands = Type::common(self.program.operand_type(left), self.program.operand_type(right));
+                self.load(left, operands, false);
+                self.load(right, operands, true);
+                if op.is_comparison() {
+                    self.compare(*op, operands);
+                } else {
+                    self.arithmetic(*op, operands);
+                }
+                self.store(*dest);
+            },
+            Kind::Negate { dest, operand } => {
+                let kind = self.program.temps[*dest];
+                self.load(operand, kind, false);
+                match kind {
+                    Type::Int => self.line("negl %eax"),
+                    Type::Float => {
+                        let mask = self.constant(Value::Float(-0.0));
+                        self.line(&format!("movss {}, %xmm1", mask));
+                        self.line("xorps %xmm1, %xmm0");
+                    },
+                    Type::Double => {
+                        let mask = self.constant(Value::Double(-0.0));
+                        self.line(&format!("movsd {}, %xmm1", mask));
+                        self.line("xorpd %xmm1, %xmm0");
+                    },
+                }
+                self.store(*dest);
+            },
+            Kind::Store { variable, value } => {
+                self.load(value, Type::Float, false);
+                self.line(&format!("movss %xmm0, v_{}(%rip)", variable));
+            },
+            Kind::Input { variable } => {
+                // if (0 == scanf("%f", &v)) { v = 0; scanf("%*s"); }
+                let done = self.local_label();
+                let format = self.string("%f");
+                self.line(&format!("leaq {}, %rdi", format));
+                self.line(&format!("leaq v_{}(%rip), %rsi", variable));
+                self.line("xorl %eax, %eax");
+                self.line("call scanf@PLT");
+                self.line("testl %eax, %eax");
+                self.line(&format!("jne {}", done));
+                self.line(&format!("movl $0, v_{}(%rip)", variable));
+                let skip = self.string("%*s");
+                self.line(&format!("leaq {}, %rdi", skip));
+                self.line("xorl %eax, %eax");
+                self.line("call scanf@PLT");
+                self.text += &format!("{}:\n", done);
+            },
+            Kind::PrintString(text) => {
+                let format = self.string(&format!("{}\\n", text));
+                self.line(&format!("leaq {}, %rdi", format));
+                self.line("xorl %eax, %eax");
+                self.line("call printf@PLT");
+            },
+            Kind::PrintValue(value) => {
+                // printf("%.2f\n", (float)(x)), the float promoted to double
+                self.load(value, Type::Float, false);
+                self.line("cvtss2sd %xmm0, %xmm0");
+                let format = self.string("%.2f\\n");
+                self.line(&format!("leaq {}, %rdi", format));
+                self.line("movl $1, %eax");
+                self.line("call printf@PLT");
+            },
+            Kind::Label(label) => self.text += &format!(".L_{}:\n", label),
+            Kind::Jump(label) => self.line(&format!("jmp .L_{}", label)),
+            Kind::JumpIfZero { condition, target } => {
+                let kind = self.program.operand_type(condition);
+                self.load(condition, kind, false);
+                match kind {
+                    Type::Int => {
+                        self.line("testl %eax, %eax");
+                        self.line(&format!("je .L_{}", target));
+                    },
+                    _ => {
+                        // NaN is not zero, so an unordered comparison does not jump
+                        let next = self.local_label();
+                        let (zero, compare) = match kind {
+                            Type::Float => ("xorps %xmm1, %xmm1", "ucomiss %xmm1, %xmm0"),
+                            _ => ("xorpd %xmm1, %xmm1", "ucomisd %xmm1, %xmm0"),
+                        };
+                        self.line(zero);
+                        self.line(compare);
+                        self.line(&format!("jp {}", next));
+                        self.line(&format!("je .L_{}", target));
+                        self.text += &format!("{}:\n", next);
+                    },
+                }
+            },
+        }
+    }
+
+    fn arithmetic(&mut self, op: Op, kind: Type) {
+        let suffix = if kind == Type::Float { "ss" } else { "sd" };
+        let instruction = match (op, kind) {
+            (Op::Add, Type::Int) => "addl %ecx, %eax".to_string(),
+            (Op::Sub, Type::Int) => "subl %ecx, %eax".to_string(),
+            (Op::Mul, Type::Int) => "imull %ecx, %eax".to_string(),
+            (Op::Div, Type::Int) => {
+                self.line("cltd");
+                "idivl %ecx".to_string()
+            },
+            (Op::Add, _) => format!("add{} %xmm1, %xmm0", suffix),
+            (Op::Sub, _) => format!("sub{} %xmm1, %xmm0", suffix),
+            (Op::Mul, _) => format!("mul{} %xmm1, %xmm0", suffix),
+            (Op::Div, _) => format!("div{} %xmm1, %xmm0", suffix),
+            _ => unreachable!("Not an arithmetic operator: {}", op.symbol()),
+        };
+        self.line(&instruction);
+    }
+
+    /// Leaves 0 or 1 in %eax. Comparisons involving NaN are false, except `!=`.
+    fn compare(&mut self, op: Op, kind: Type) {
+        if kind == Type::Int {
+            self.line("cmpl %ecx, %eax");
+            let set = match op {
+                Op::Eq => "sete",
+                Op::Ne => "setne",
+                Op::Lt => "setl",
+                Op::Le => "setle",
+                Op::Gt => "setg",
+                _ => "setge",
+            };
+            self.line(&format!("{} %al", set));
+        } else {
+            let compare = if kind == Type::Float { "ucomiss" } else { "ucomisd" };
+            // `a` and `ae` are false when unordered, so < and <= swap the operands
+            match op {
+                Op::Lt | Op::Le => self.line(&format!("{} %xmm0, %xmm1", compare)),
+                _ => self.line(&format!("{} %xmm1, %xmm0", compare)),
+            }
+            match op {
+                Op::Eq => {
+                    self.line("sete %al");
+                    self.line("setnp %cl");
+                    self.line("andb %cl, %al");
+                },
+                Op::Ne => {
+                    self.line("setne %al");
+                    self.line("setp %cl");
+                    self.line("orb %cl, %al");
+                },
+                Op::Lt | Op::Gt => self.line("seta %al"),
+                _ => self.line("setae %al"),
+            }
+        }
+        self.line("movzbl %al, %eax");
+    }
+
+    fn finish(self) -> String {
+        let mut output = String::from("    .text\n    .globl main\n    .type main, @function\nmain:\n");
+        let frame = (8 * self.program.temps.len()).div_ceil(16) * 16;
+        output += "    pushq %rbp\n    movq %rsp, %rbp\n";
+        if frame > 0 {
+            output += &format!("    subq ${}, %rsp\n", frame);
+        }
+        output += &self.text;
+        output += "    xorl %eax, %eax\n    leave\n    ret\n    .size main, .-main\n";
+
+        output += "\n    .data\n";
+        for variable in self.program.variables() {
+            output += &format!("    .align 4\nv_{}:\n    .float 0\n", variable);
+        }
+
+        output += "\n    .section .rodata\n";
+        for (index, constant) in self.constants.iter().enumerate() {
+            // the bit patterns, which keep infinities, NaN and -0.0 exact
+            let (align, directive) = match constant {
+                Value::Int(value) => (4, format!(".long {}", value)),
+                Value::Float(value) => (4, format!(".long {:#x}", value.to_bits())),
+                Value::Double(value) => (8, format!(".quad {:#x}", value.to_bits())),
+            };
+            output += &format!("    .align {}\n.LC{}:\n    {}\n", align, index, directive);
+        }
+        for (index, string) in self.strings.iter().enumerate() {
+            output += &format!(".LS{}:\n    .string \"{}\"\n", index, string);
+        }
+
+        output += "\n    .section .note.GNU-stack,\"\",@progbits\n";
+        output
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs;
+
+use compiler::bytecode::{Chunk, Opcode};
+use compiler::{Options, OptionsBuilder, Target};
+
+mod common;
+
+/*
+The .bbc file format
+
+Every sample that compiles must read back to a chunk that writes out the
+same bytes, and files the compiler could not have written must be rejected
+before the VM sees them.
+*/
+
+fn bytecode(source: &str, options: OptionsBuilder) -> Vec<u8> {
+    let options = options.target(Target::Bytecode).build().unwrap();
+    compiler::compile(source, &options).unwrap().code
+}
+
+#[test]
+fn round_trip() {
+    for program in common::programs() {
+        let source = fs::read_to_string(&program).unwrap();
+        if compiler::parse(&source).is_err() {
+            continue;
+        }
+        let bytes = bytecode(&source, common::options(&program));
+        let chunk = Chunk::from_bytes(&bytes).unwrap_or_else(|message| panic!("{}: {message}", program.display()));
+        assert!(chunk.to_bytes() == bytes, "{}: does not read back the same", program.display());
+    }
+}
+
+#[test]
+fn rejects_bad_files() {
+    let bytes = bytecode("INPUT a\nLET b = a * 2\nPRINT \"b is\"\nPRINT b\n", Options::builder());
+    let chunk = Chunk::from_bytes(&bytes).unwrap();
+
+    assert!(Chunk::from_bytes(b"BBC").is_err());
+    assert!(Chunk::from_bytes(b"BBC\x02\0\0\0\0").is_err());
+    assert!(Chunk::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Chunk::from_bytes(&[&bytes[..], b"\0"].concat()).is_err());
+
+    // a slot count no instruction could use
+    let mut huge = bytes.clone();
+    huge[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Chunk::from_bytes(&huge).is_err());
+
+    // operands out of range
+    let out_of_range = [
+        Opcode::Constant(chunk.constants.len() as u32),
+        Opcode::Load(chunk.slots),
+        Opcode::Store(chunk.slots),
+        Opcode::PrintString(chunk.strings.len() as u32),
+        Opcode::Jump(chunk.code.len() as u32),
+    ];
+    for opcode in out_of_range {
+        let mut code = chunk.code.clone();
+        code[0].0 = opcode;
+        let bad = Chunk { slots: chunk.slots, constants: chunk.constants.clone(), strings: chunk.strings.clone(), code };
+        assert!(Chunk::from_bytes(&bad.to_bytes()).is_err(), "{:?} is out of range", opcode);
+    }
+}
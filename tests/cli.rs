@@ -0,0 +1,54 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{self, Command, Output, Stdio};
+
+/*
+The command line
+
+`interpret` runs a program without a C compiler. `run`, which used to do
+that, builds the program with the C compiler now and says to use
+`interpret` when there is none.
+*/
+
+const PROGRAM: &str = "INPUT n\nLET total = 0\nWHILE n > 0 REPEAT\n    LET total = total + n\n    LET n = n - 1\nENDWHILE\nPRINT total\n";
+
+/// Writes `PROGRAM` to a temporary file for the test called `label`.
+fn source(label: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("basic-cli-{}-{}.bas", label, process::id()));
+    fs::write(&path, PROGRAM).unwrap();
+    path
+}
+
+fn compiler(args: &[&str], cc: Option<&str>, stdin: &[u8]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_compiler"));
+    command.args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    if let Some(cc) = cc {
+        command.env("CC", cc);
+    }
+    let mut child = command.spawn().unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn interpret() {
+    let source = source("interpret");
+    let output = compiler(&["interpret", source.to_str().unwrap()], None, b"4\n");
+    let _ = fs::remove_file(&source);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "10.00\n");
+}
+
+#[test]
+fn run_without_a_c_compiler() {
+    let source = source("run");
+    let executable = source.with_extension("out");
+    let args = ["run", "-o", executable.to_str().unwrap(), source.to_str().unwrap()];
+    let output = compiler(&args, Some("/nonexistent/cc"), b"");
+    let _ = fs::remove_file(&source);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("could not run /nonexistent/cc") && stderr.contains("`interpret`"), "{stderr}");
+}
@@ -0,0 +1,32 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use compiler::{Options, OptionsBuilder};
+
+/// The sample programs, `tests/programs/*.bas`.
+pub fn programs() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut programs: Vec<PathBuf> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "bas"))
+        .collect();
+    programs.sort();
+    programs
+}
+
+/// The options a program is checked with: the defaults, changed by the
+/// command line flags in `NAME.flags` (`-O0`, `--pass=fold`, ...) when there is one.
+pub fn options(program: &Path) -> OptionsBuilder {
+    let flags = fs::read_to_string(program.with_extension("flags")).unwrap_or_default();
+    let mut builder = Options::builder();
+    for flag in flags.split_whitespace() {
+        builder = match flag {
+            "-O0" | "-O1" | "-O2" => builder.opt_level(flag[2..].parse().unwrap()),
+            _ if flag.starts_with("--pass=") => builder.enable_pass(&flag["--pass=".len()..]),
+            _ if flag.starts_with("--disable-pass=") => builder.disable_pass(&flag["--disable-pass=".len()..]),
+            _ => panic!("{}: unknown flag {flag}", program.display()),
+        };
+    }
+    builder
+}
@@ -0,0 +1,287 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+use compiler::bytecode::Chunk;
+use compiler::interp::Interpreter;
+use compiler::vm::Vm;
+use compiler::wat;
+use compiler::{Options, OptionsBuilder, Output, Target};
+
+mod common;
+
+/*
+End-to-end tests
+
+Each `tests/programs/NAME.bas` with a `NAME.stdout` is compiled to C, built
+with `$CC` (cc when unset) and run with `NAME.stdin`, or nothing when there
+is none, as its input. What it prints must match `NAME.stdout` at -O0 and
+-O1, which must not change any result, and with the flags in `NAME.flags`
+when there is one, so a pass that changes what a program does shows up here
+even when its C looks right.
+
+The interpreter, as the reference implementation, has to print the same, and
+so does the bytecode VM, running the .bbc file read back from its bytes, and
+the WebAssembly text module, loaded and run by the `wat` runner. On
+x86-64 Linux the assembly output is built and run as well, and the LLVM IR
+runs under `lli` when there is one, the Python under python3, the
+JavaScript under node and the Rust is built with rustc.
+
+Without a working C compiler or interpreter those tests pass with a note on
+stderr.
+`BLESS=1` rewrites the .stdout files from the unoptimized C.
+*/
+
+fn c_compiler() -> Option<String> {
+    let command = env::var("CC").ok().filter(|command| !command.trim().is_empty());
+    let command = command.unwrap_or_else(|| "cc".to_string());
+    let works = Command::new(&command)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    works.then_some(command)
+}
+
+/// How to run LLVM IR with opaque pointers: `$LLI`, or lli when unset, with
+/// `-opaque-pointers` when it is LLVM 14.
+fn lli() -> Option<(String, Vec<&'static Path>)> {
+    let command = env::var("LLI").ok().filter(|command| !command.trim().is_empty());
+    let command = command.unwrap_or_else(|| "lli".to_string());
+    let probe = env::temp_dir().join(format!("basic-lli-{}.ll", process::id()));
+    fs::write(&probe, "define i32 @main() {\n  %p = alloca ptr\n  ret i32 0\n}\n").unwrap();
+    let flags = [vec![], vec![Path::new("-opaque-pointers")]].into_iter().find(|flags| {
+        Command::new(&command)
+            .args(flags)
+            .arg(&probe)
+            .output()
+            .is_ok_and(|output| output.status.success())
+    });
+    let _ = fs::remove_file(&probe);
+    flags.map(|flags| (command, flags))
+}
+
+/// Whether `command` runs, for the interpreters of the transpiled programs.
+fn available(command: &str) -> bool {
+    Command::new(command).arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+/// The programs with an expected output.
+fn runnable() -> Vec<PathBuf> {
+    common::programs().into_iter().filter(|path| path.with_extension("stdout").exists()).collect()
+}
+
+fn compile(program: &Path, options: OptionsBuilder) -> Result<Output, String> {
+    let source = fs::read_to_string(program).unwrap();
+    compiler::compile(&source, &options.build()?).map_err(|diagnostics| {
+        diagnostics.iter().map(|diagnostic| format!("{}\n", diagnostic)).collect()
+    })
+}
+
+/// A temporary file for `program`, unique to this test run.
+fn scratch(program: &Path, label: &str, extension: &str) -> PathBuf {
+    let stem = program.file_stem().unwrap().to_string_lossy();
+    env::temp_dir().join(format!("basic-{}-{}-{}.{}", stem, label, process::id(), extension))
+}
+
+/// Runs `command` with `arguments` on `stdin`, returning what it printed.
+fn run(command: &Path, arguments: &[&Path], stdin: &[u8]) -> Result<String, String> {
+    let mut child = Command::new(command)
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("could not run {}: {}", command.display(), error))?;
+    // the program may exit without reading everything
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    let ran = child.wait_with_output().map_err(|error| format!("could not run {}: {}", command.display(), error))?;
+    if !ran.status.success() {
+        return Err(format!("the program failed ({})", ran.status));
+    }
+    Ok(String::from_utf8_lossy(&ran.stdout).into_owned())
+}
+
+/// Builds `source` (C or assembly with the C compiler, or Rust with rustc)
+/// and runs it on `stdin`.
+fn build_and_run(compiler: &str, source: &Path, stdin: &[u8]) -> Result<String, String> {
+    let executable = source.with_extension("out");
+    let built = Command::new(compiler).arg(source).arg("-o").arg(&executable).output();
+    let _ = fs::remove_file(source);
+    let built = built.map_err(|error| format!("could not run {compiler}: {error}"))?;
+    if !built.status.success() {
+        return Err(format!("{compiler} failed:\n{}", String::from_utf8_lossy(&built.stderr)));
+    }
+    let ran = run(&executable, &[], stdin);
+    let _ = fs::remove_file(&executable);
+    ran
+}
+
+/// Transpiles `program` to `target` and runs it with `interpreter` on `stdin`.
+fn run_script(program: &Path, target: Target, interpreter: &str, stdin: &[u8]) -> Result<String, String> {
+    let output = compile(program, Options::builder().target(target))?;
+    let script = scratch(program, interpreter, "script");
+    fs::write(&script, &output.code).unwrap();
+    let ran = run(Path::new(interpreter), &[&script], stdin);
+    let _ = fs::remove_file(&script);
+    ran
+}
+
+/// Runs each of `programs` with `run` and compares what it printed with
+/// `NAME.stdout`, returning the differences. With `bless`, updates the
+/// .stdout files instead.
+fn check(
+    label: &str,
+    programs: &[PathBuf],
+    bless: bool,
+    run: impl Fn(&Path, &[u8]) -> Result<String, String>,
+) -> Vec<String> {
+    let mut failures = vec![];
+    for program in programs {
+        let name = program.file_name().unwrap().to_string_lossy();
+        let stdin = fs::read(program.with_extension("stdin")).unwrap_or_default();
+        let golden = program.with_extension("stdout");
+        let expected = fs::read_to_string(&golden).unwrap();
+        match run(program, &stdin) {
+            Ok(actual) if actual == expected => {},
+            Ok(actual) if bless => fs::write(&golden, actual).unwrap(),
+            Ok(actual) => {
+                failures.push(format!("{name} {label}: differs\n--- expected\n{expected}--- actual\n{actual}"));
+            },
+            Err(message) => failures.push(format!("{name} {label}: {message}")),
+        }
+    }
+    failures
+}
+
+fn assert_passed(failures: Vec<String>) {
+    assert!(
+        failures.is_empty(),
+        "{}\n\nrun with BLESS=1 to accept the new output",
+        failures.join("\n\n"),
+    );
+}
+
+#[test]
+fn c() {
+    let Some(cc) = c_compiler() else {
+        return eprintln!("skipping: no working C compiler, set CC to use one");
+    };
+    let bless = env::var("BLESS").is_ok_and(|value| value == "1");
+    let programs = runnable();
+    let mut failures = vec![];
+    for level in [0, 1] {
+        let label = format!("O{level}");
+        failures.extend(check(&format!("at -{label}"), &programs, bless && level == 0, |program, stdin| {
+            let output = compile(program, Options::builder().opt_level(level))?;
+            let c_file = scratch(program, &label, "c");
+            fs::write(&c_file, &output.code).unwrap();
+            build_and_run(&cc, &c_file, stdin)
+        }));
+    }
+    let flagged: Vec<PathBuf> = programs.into_iter().filter(|path| path.with_extension("flags").exists()).collect();
+    failures.extend(check("with its flags", &flagged, false, |program, stdin| {
+        let output = compile(program, common::options(program))?;
+        let c_file = scratch(program, "flags", "c");
+        fs::write(&c_file, &output.code).unwrap();
+        build_and_run(&cc, &c_file, stdin)
+    }));
+    assert_passed(failures);
+}
+
+#[test]
+fn interpreter() {
+    assert_passed(check("interpreted", &runnable(), false, |program, stdin| {
+        let source = fs::read_to_string(program).unwrap();
+        let ast = compiler::parse(&source).map_err(|diagnostics| format!("{}", diagnostics[0]))?;
+        let mut stdout = vec![];
+        Interpreter::new(stdin, &mut stdout).run(&ast).map_err(|diagnostic| diagnostic.to_string())?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }));
+}
+
+#[test]
+fn vm() {
+    assert_passed(check("on the VM", &runnable(), false, |program, stdin| {
+        let output = compile(program, Options::builder().target(Target::Bytecode))?;
+        let chunk = Chunk::from_bytes(&output.code)?;
+        let mut stdout = vec![];
+        Vm::new(stdin, &mut stdout).run(&chunk).map_err(|diagnostic| diagnostic.to_string())?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }));
+}
+
+#[test]
+fn wasm() {
+    assert_passed(check("as WebAssembly", &runnable(), false, |program, stdin| {
+        let output = compile(program, Options::builder().target(Target::Wasm))?;
+        let module = wat::Module::load(output.text().unwrap())?;
+        let mut stdout = vec![];
+        wat::Runner::new(stdin, &mut stdout).run(&module)?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }));
+}
+
+#[test]
+fn x86_64() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return eprintln!("skipping: the assembly is for x86-64 Linux");
+    }
+    let Some(cc) = c_compiler() else {
+        return eprintln!("skipping: no working C compiler, set CC to use one");
+    };
+    assert_passed(check("as x86-64 assembly", &runnable(), false, |program, stdin| {
+        let output = compile(program, Options::builder().target(Target::X86_64Asm))?;
+        let assembly = scratch(program, "x86_64", "s");
+        fs::write(&assembly, &output.code).unwrap();
+        build_and_run(&cc, &assembly, stdin)
+    }));
+}
+
+#[test]
+fn llvm() {
+    let Some((lli, flags)) = lli() else {
+        return eprintln!("skipping: no working lli, set LLI to use one");
+    };
+    assert_passed(check("as LLVM IR", &runnable(), false, |program, stdin| {
+        let output = compile(program, Options::builder().target(Target::LlvmIr))?;
+        let module = scratch(program, "llvm", "ll");
+        fs::write(&module, &output.code).unwrap();
+        let ran = run(Path::new(&lli), &[&flags[..], &[module.as_path()]].concat(), stdin);
+        let _ = fs::remove_file(&module);
+        ran
+    }));
+}
+
+#[test]
+fn python() {
+    if !available("python3") {
+        return eprintln!("skipping: no python3");
+    }
+    assert_passed(check("in Python", &runnable(), false, |program, stdin| {
+        run_script(program, Target::Python, "python3", stdin)
+    }));
+}
+
+#[test]
+fn javascript() {
+    if !available("node") {
+        return eprintln!("skipping: no node");
+    }
+    assert_passed(check("in JavaScript", &runnable(), false, |program, stdin| {
+        run_script(program, Target::JavaScript, "node", stdin)
+    }));
+}
+
+#[test]
+fn rust() {
+    if !available("rustc") {
+        return eprintln!("skipping: no rustc");
+    }
+    assert_passed(check("in Rust", &runnable(), false, |program, stdin| {
+        let output = compile(program, Options::builder().target(Target::Rust))?;
+        let source = scratch(program, "rust", "rs");
+        fs::write(&source, &output.code).unwrap();
+        build_and_run("rustc", &source, stdin)
+    }));
+}
@@ -0,0 +1,85 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use compiler::lex::{Lexer, TokenType};
+
+mod common;
+
+/*
+Golden-file tests over the sample programs
+
+Each `tests/programs/NAME.bas` is checked against the files next to it:
+
+    NAME.tokens        every token the lexer produces, up to EOF or its error
+    NAME.diagnostics   the warnings, or the errors that stop the compile
+    NAME.c             the C, when it compiles
+
+The C is compiled at the default options, or with the flags in NAME.flags
+when there is one. A program that doesn't compile has no NAME.c. After a change meant to alter
+the output, `BLESS=1 cargo test --test golden` rewrites the files to match,
+to be reviewed in the diff like any other change.
+*/
+
+fn tokens(source: &str) -> String {
+    let mut lexer = Lexer::new(source.to_string());
+    let mut dump = String::new();
+    loop {
+        match lexer.get_token() {
+            Ok(token) => {
+                dump += &format!("{} {} {:?}\n", token.line, token.kind, token.text);
+                if token.kind == TokenType::EOF {
+                    return dump;
+                }
+            },
+            Err(diagnostic) => return dump + &format!("{}\n", diagnostic),
+        }
+    }
+}
+
+/// The outputs to compare, by the extension of their golden file.
+fn outputs(program: &Path, source: &str) -> Vec<(&'static str, Option<String>)> {
+    let options = common::options(program).build().unwrap();
+    let (code, diagnostics) = match compiler::compile(source, &options) {
+        Ok(output) => (Some(output.text().unwrap().to_string()), output.diagnostics),
+        Err(diagnostics) => (None, diagnostics),
+    };
+    let diagnostics: String = diagnostics.iter().map(|diagnostic| format!("{}\n", diagnostic)).collect();
+    vec![("tokens", Some(tokens(source))), ("diagnostics", Some(diagnostics)), ("c", code)]
+}
+
+#[test]
+fn golden() {
+    let bless = env::var("BLESS").is_ok_and(|value| value == "1");
+    let mut failures = vec![];
+    for program in common::programs() {
+        let source = fs::read_to_string(&program).unwrap();
+        for (extension, actual) in outputs(&program, &source) {
+            let golden = program.with_extension(extension);
+            let expected = fs::read_to_string(&golden).ok();
+            if expected == actual {
+                continue;
+            }
+            if bless {
+                match &actual {
+                    Some(text) => fs::write(&golden, text).unwrap(),
+                    None => fs::remove_file(&golden).unwrap(),
+                }
+                continue;
+            }
+            let name = golden.file_name().unwrap().to_string_lossy().into_owned();
+            match (expected, actual) {
+                (None, _) => failures.push(format!("{name}: missing")),
+                (Some(_), None) => failures.push(format!("{name}: the program no longer compiles")),
+                (Some(expected), Some(actual)) => {
+                    failures.push(format!("{name}: differs\n--- expected\n{expected}--- actual\n{actual}"));
+                },
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n\nrun with BLESS=1 to accept the new output",
+        failures.join("\n\n"),
+    );
+}
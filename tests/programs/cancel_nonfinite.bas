@@ -0,0 +1,17 @@
+# Terms with a variable never cancel, since the variable may be infinite or NaN.
+INPUT x
+LET a = x * 0
+LET b = x - x
+LET c = 2 * x - x - x + 1
+IF a != a THEN
+    PRINT "x * 0 is NaN"
+ENDIF
+IF b != b THEN
+    PRINT "x - x is NaN"
+ENDIF
+IF c != c THEN
+    PRINT "2 * x - x - x + 1 is NaN"
+ENDIF
+INPUT y
+PRINT y * 0 + y - y
+PRINT 3 * y - y * 2 + 1
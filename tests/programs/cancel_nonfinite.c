@@ -0,0 +1,34 @@
+#include <stdio.h>
+
+int main(void) {
+    float x;
+    float a;
+    float b;
+    float c;
+    float y;
+
+    if (0 == scanf("%f", &x)) {
+        x = 0;
+        scanf("%*s");
+    }
+    a = x * 0;
+    b = x - x;
+    c = 2 * x - x - x + 1;
+    if (!(a != a)) goto endif_0;
+    printf("x * 0 is NaN\n");
+endif_0:
+    if (!(b != b)) goto endif_1;
+    printf("x - x is NaN\n");
+endif_1:
+    if (!(c != c)) goto endif_2;
+    printf("2 * x - x - x + 1 is NaN\n");
+endif_2:
+    if (0 == scanf("%f", &y)) {
+        y = 0;
+        scanf("%*s");
+    }
+    printf("%.2f\n", (float)(y * 0 + y - y));
+    printf("%.2f\n", (float)(y + 1));
+    
+    return 0;
+}
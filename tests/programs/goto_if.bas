@@ -0,0 +1,19 @@
+# GOTO into and out of IF bodies, nested IFs and a label ending the program.
+LET n = 0
+LABEL top
+LET n = n + 1
+IF n < 3 THEN
+    PRINT "inside the IF"
+    IF n == 1 THEN
+        PRINT n
+        GOTO top
+    ENDIF
+    PRINT "after the nested IF"
+ENDIF
+IF n < 5 THEN
+    GOTO top
+ENDIF
+PRINT n
+GOTO done
+PRINT "skipped"
+LABEL done
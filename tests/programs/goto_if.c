@@ -0,0 +1,23 @@
+#include <stdio.h>
+
+int main(void) {
+    float n;
+
+    n = 0;
+top:
+    n = n + 1;
+    if (!(n < 3)) goto endif_0;
+    printf("inside the IF\n");
+    if (!(n == 1)) goto endif_1;
+    printf("%.2f\n", (float)(n));
+    goto top;
+endif_1:
+    printf("after the nested IF\n");
+endif_0:
+    if (!(n < 5)) goto endif_2;
+    goto top;
+endif_2:
+    printf("%.2f\n", (float)(n));
+    
+    return 0;
+}
@@ -0,0 +1,21 @@
+# INPUT reads hexadecimal floats, as glibc's scanf("%f") does.
+INPUT a
+INPUT b
+INPUT c
+INPUT d
+INPUT e
+INPUT f
+INPUT g
+INPUT h
+INPUT i
+INPUT j
+PRINT a
+PRINT b
+PRINT c
+PRINT d
+PRINT e
+PRINT f
+PRINT g
+PRINT h
+PRINT i
+PRINT j
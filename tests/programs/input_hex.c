@@ -0,0 +1,67 @@
+#include <stdio.h>
+
+int main(void) {
+    float a;
+    float b;
+    float c;
+    float d;
+    float e;
+    float f;
+    float g;
+    float h;
+    float i;
+    float j;
+
+    if (0 == scanf("%f", &a)) {
+        a = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &b)) {
+        b = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &c)) {
+        c = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &d)) {
+        d = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &e)) {
+        e = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &f)) {
+        f = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &g)) {
+        g = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &h)) {
+        h = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &i)) {
+        i = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &j)) {
+        j = 0;
+        scanf("%*s");
+    }
+    printf("%.2f\n", (float)(a));
+    printf("%.2f\n", (float)(b));
+    printf("%.2f\n", (float)(c));
+    printf("%.2f\n", (float)(d));
+    printf("%.2f\n", (float)(e));
+    printf("%.2f\n", (float)(f));
+    printf("%.2f\n", (float)(g));
+    printf("%.2f\n", (float)(h));
+    printf("%.2f\n", (float)(i));
+    printf("%.2f\n", (float)(j));
+    
+    return 0;
+}
@@ -0,0 +1,11 @@
+# INPUT reads numbers separated by any whitespace, gives 0 for a word that
+# is not a number and leaves the variable alone at the end of input.
+LET a = 9
+INPUT x
+INPUT y
+INPUT z
+INPUT a
+PRINT x
+PRINT y
+PRINT z
+PRINT a
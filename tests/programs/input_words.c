@@ -0,0 +1,32 @@
+#include <stdio.h>
+
+int main(void) {
+    float a;
+    float x;
+    float y;
+    float z;
+
+    a = 9;
+    if (0 == scanf("%f", &x)) {
+        x = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &y)) {
+        y = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &z)) {
+        z = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &a)) {
+        a = 0;
+        scanf("%*s");
+    }
+    printf("%.2f\n", (float)(x));
+    printf("%.2f\n", (float)(y));
+    printf("%.2f\n", (float)(z));
+    printf("%.2f\n", (float)(a));
+    
+    return 0;
+}
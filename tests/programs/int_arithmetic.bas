@@ -0,0 +1,10 @@
+# Integer literals are 32-bit C ints, whatever the optimization level.
+PRINT 100000 * 100000
+PRINT &HFFFF * &HFFFF
+PRINT 2147483647 - 1 + 1
+PRINT 3000000000 * 2
+PRINT 7 / 2 * 2.5 + 1 / 3
+PRINT 9.5 - 7 / 2 * 2 + 1.0 / 4
+LET x = 3
+PRINT 7 / 2 * x + 1 / 2
+PRINT &B1111 / 4 * 1e1 - x
@@ -0,0 +1,15 @@
+#include <stdio.h>
+
+int main(void) {
+
+    printf("%.2f\n", (float)(100000 * 100000));
+    printf("%.2f\n", (float)(65535 * 65535));
+    printf("%.2f\n", (float)(2147483647));
+    printf("%.2f\n", (float)(6000000000.0));
+    printf("%.2f\n", (float)(7.5));
+    printf("%.2f\n", (float)(3.75));
+    printf("%.2f\n", (float)(9.0f));
+    printf("%.2f\n", (float)(27.0));
+    
+    return 0;
+}
@@ -0,0 +1,10 @@
+# Overflowing int arithmetic is reported and left for the program, where it wraps.
+PRINT 2147483647 + 1
+PRINT -2147483647 - 1 - 1
+PRINT 65536 * 32768
+PRINT -2147483647 - 1
+LET x = -2147483647 - 1
+PRINT x
+IF -2147483647 - 1 - 1 > 0 THEN
+    PRINT "wrapped around"
+ENDIF
@@ -0,0 +1,17 @@
+#include <stdio.h>
+
+int main(void) {
+    int t_1;
+
+    printf("%.2f\n", (float)(2147483647 + 1));
+    t_1 = (-2147483647 - 1) - 1;
+    printf("%.2f\n", (float)(t_1));
+    printf("%.2f\n", (float)(65536 * 32768));
+    printf("%.2f\n", (float)((-2147483647 - 1)));
+    printf("%.2f\n", (float)(-2147483600.0f));
+    if (!(t_1 > 0)) goto endif_0;
+    printf("wrapped around\n");
+endif_0:
+    
+    return 0;
+}
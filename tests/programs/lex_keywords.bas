@@ -0,0 +1 @@
+IF+-123 foo*THEN/
\ No newline at end of file
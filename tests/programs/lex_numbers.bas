@@ -0,0 +1 @@
++-123 9.8654*/
\ No newline at end of file
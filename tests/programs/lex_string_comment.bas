@@ -0,0 +1,2 @@
++- "This is a string" # This is a comment!
+ */
\ No newline at end of file
@@ -0,0 +1,3 @@
+# A NaN that constant propagation can prove is folded, keeping its sign.
+LET big = 1e39
+PRINT big - big
@@ -0,0 +1,9 @@
+#include <math.h>
+#include <stdio.h>
+
+int main(void) {
+
+    printf("%.2f\n", (float)(-NAN));
+    
+    return 0;
+}
@@ -0,0 +1,4 @@
+# Negating a negative constant.
+LET x = -2
+PRINT -x
+PRINT 1 - -x
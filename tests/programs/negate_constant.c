@@ -0,0 +1,11 @@
+#include <stdio.h>
+
+int main(void) {
+    float x;
+
+    x = -2;
+    printf("%.2f\n", (float)(-(-2.0f)));
+    printf("%.2f\n", (float)(1 - -(-2.0f)));
+    
+    return 0;
+}
@@ -0,0 +1,10 @@
+# Zero stored in a variable is a float 0, whose negation prints as -0.00.
+LET q = 0
+PRINT q * -1
+LET r = 2 - 2
+PRINT r * -1
+PRINT 0 * -1
+PRINT -0
+LET s = 0 * -1
+PRINT -s
+PRINT 7 / 2
@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+int main(void) {
+
+    printf("%.2f\n", (float)(-0.0f));
+    printf("%.2f\n", (float)(-0.0f));
+    printf("%.2f\n", (float)(0));
+    printf("%.2f\n", (float)(0));
+    printf("%.2f\n", (float)(-0.0f));
+    printf("%.2f\n", (float)(3));
+    
+    return 0;
+}
@@ -0,0 +1,9 @@
+# Names the transpiled languages predefine are renamed there.
+INPUT None
+LET Some = None + 1
+LET Ok = Some * 2
+LET Err = Ok - None
+PRINT None
+PRINT Some
+PRINT Ok
+PRINT Err
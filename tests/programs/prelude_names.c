@@ -0,0 +1,22 @@
+#include <stdio.h>
+
+int main(void) {
+    float None;
+    float Some;
+    float Ok;
+    float Err;
+
+    if (0 == scanf("%f", &None)) {
+        None = 0;
+        scanf("%*s");
+    }
+    Some = None + 1;
+    Ok = Some * 2;
+    Err = Ok - None;
+    printf("%.2f\n", (float)(None));
+    printf("%.2f\n", (float)(Some));
+    printf("%.2f\n", (float)(Ok));
+    printf("%.2f\n", (float)(Err));
+    
+    return 0;
+}
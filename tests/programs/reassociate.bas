@@ -0,0 +1,4 @@
+# Without -O2 arithmetic is not reassociated, which could change the result.
+INPUT x
+PRINT x + 100000000 - 100000000
+PRINT 2 * x * 3
@@ -0,0 +1,14 @@
+#include <stdio.h>
+
+int main(void) {
+    float x;
+
+    if (0 == scanf("%f", &x)) {
+        x = 0;
+        scanf("%*s");
+    }
+    printf("%.2f\n", (float)(x + 100000000 - 100000000));
+    printf("%.2f\n", (float)(2 * x * 3));
+    
+    return 0;
+}
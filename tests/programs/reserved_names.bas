@@ -0,0 +1,12 @@
+# Names that are keywords or library names in C are renamed in the C.
+INPUT asm
+LET int = asm * 2
+LET printf = int + 1
+LABEL main
+IF printf < 10 THEN
+    LET printf = printf * 2
+    GOTO main
+ENDIF
+PRINT asm
+PRINT int
+PRINT printf
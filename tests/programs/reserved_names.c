@@ -0,0 +1,24 @@
+#include <stdio.h>
+
+int main(void) {
+    float asm_;
+    float int_;
+    float printf_;
+
+    if (0 == scanf("%f", &asm_)) {
+        asm_ = 0;
+        scanf("%*s");
+    }
+    int_ = asm_ * 2;
+    printf_ = int_ + 1;
+main_:
+    if (!(printf_ < 10)) goto endif_0;
+    printf_ = printf_ * 2;
+    goto main_;
+endif_0:
+    printf("%.2f\n", (float)(asm_));
+    printf("%.2f\n", (float)(int_));
+    printf("%.2f\n", (float)(printf_));
+    
+    return 0;
+}
@@ -0,0 +1,10 @@
+#include <stdio.h>
+
+int main(void) {
+
+    printf("hello, world!\n");
+    printf("second line\n");
+    printf("and a third...\n");
+    
+    return 0;
+}
@@ -0,0 +1,10 @@
+# Constant folding must agree with the C the compiler emits:
+# integer literals divide like C ints, decimals are doubles, variables are floats.
+LET x = 2
+PRINT 1 / 3
+PRINT 1.0 / 3
+PRINT 9.8654 * 2 / 4
+PRINT 7 / 2 * 2.5
+PRINT -3 * 0.5 + 1
+PRINT x / 2 * 3
+PRINT 10 - 2 + x - 1 + 2
@@ -0,0 +1,14 @@
+#include <stdio.h>
+
+int main(void) {
+
+    printf("%.2f\n", (float)(0));
+    printf("%.2f\n", (float)(0.3333333333333333));
+    printf("%.2f\n", (float)(4.9327));
+    printf("%.2f\n", (float)(7.5));
+    printf("%.2f\n", (float)(-0.5));
+    printf("%.2f\n", (float)(3.0f));
+    printf("%.2f\n", (float)(11.0f));
+    
+    return 0;
+}
@@ -0,0 +1,15 @@
+# Algebraic simplification across a whole expression.
+INPUT x
+INPUT y
+PRINT 2 * x * 3
+PRINT 1 + x + 2
+PRINT x * 1 + 0
+PRINT x * 0 + y
+PRINT x - x
+PRINT y - -x
+PRINT 2 * x + x * 3 - y / 2
+PRINT 7 / x / 2
+WHILE x * 1 > y - y REPEAT
+    LET x = x - 1 - 1
+ENDWHILE
+PRINT x
@@ -0,0 +1,33 @@
+#include <stdio.h>
+
+int main(void) {
+    float x;
+    float y;
+    float t_10;
+
+    if (0 == scanf("%f", &x)) {
+        x = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &y)) {
+        y = 0;
+        scanf("%*s");
+    }
+    printf("%.2f\n", (float)(6 * x));
+    printf("%.2f\n", (float)(x + 3));
+    printf("%.2f\n", (float)(x));
+    printf("%.2f\n", (float)(x * 0 + y));
+    printf("%.2f\n", (float)(x - x));
+    printf("%.2f\n", (float)(y + x));
+    printf("%.2f\n", (float)(5 * x - 0.5 * y));
+    printf("%.2f\n", (float)(3.5 / x));
+    t_10 = y - y;
+while_0:
+    if (!(x > t_10)) goto endwhile_1;
+    x = x - 2;
+    goto while_0;
+endwhile_1:
+    printf("%.2f\n", (float)(x));
+    
+    return 0;
+}
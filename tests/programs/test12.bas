@@ -0,0 +1,23 @@
+# Constant and copy propagation across statements.
+LET a = 3
+LET b = a * 2
+PRINT b
+LET c = b
+PRINT c + 1
+IF a > 2 THEN
+    LET d = 1
+ENDIF
+PRINT d
+INPUT a
+PRINT a + b
+LET i = 0
+WHILE i < 3 REPEAT
+    PRINT b
+    LET i = i + 1
+ENDWHILE
+LABEL again
+PRINT c
+LET c = c - 1
+IF c > 0 THEN
+    GOTO again
+ENDIF
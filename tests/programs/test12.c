@@ -0,0 +1,33 @@
+#include <stdio.h>
+
+int main(void) {
+    float a;
+    float c;
+    float i;
+
+    a = 3;
+    printf("%.2f\n", (float)(6.0f));
+    c = 6.0f;
+    printf("%.2f\n", (float)(7.0f));
+    printf("%.2f\n", (float)(1.0f));
+    if (0 == scanf("%f", &a)) {
+        a = 0;
+        scanf("%*s");
+    }
+    printf("%.2f\n", (float)(a + 6.0f));
+    i = 0;
+while_1:
+    if (!(i < 3)) goto endwhile_2;
+    printf("%.2f\n", (float)(6.0f));
+    i = i + 1;
+    goto while_1;
+endwhile_2:
+again:
+    printf("%.2f\n", (float)(c));
+    c = c - 1;
+    if (!(c > 0)) goto endif_3;
+    goto again;
+endif_3:
+    
+    return 0;
+}
@@ -0,0 +1,20 @@
+# Dead code elimination.
+LET unused = 42
+INPUT n
+IF 0 > 1 THEN
+    PRINT "never"
+ENDIF
+WHILE 0 > 1 REPEAT
+    PRINT "never either"
+ENDWHILE
+IF 1 < 2 THEN
+    PRINT "always"
+ENDIF
+LET x = 1
+LET x = n * 2
+PRINT x
+GOTO done
+PRINT "skipped"
+LET n = 0
+LABEL done
+PRINT n
@@ -0,0 +1,17 @@
+#include <stdio.h>
+
+int main(void) {
+    float n;
+    float x;
+
+    if (0 == scanf("%f", &n)) {
+        n = 0;
+        scanf("%*s");
+    }
+    printf("always\n");
+    x = n * 2;
+    printf("%.2f\n", (float)(x));
+    printf("%.2f\n", (float)(n));
+    
+    return 0;
+}
@@ -0,0 +1,20 @@
+# Loop-invariant and redundant arithmetic.
+
+INPUT rate
+INPUT n
+LET scale = 1
+LET i = 0
+LET total = 0
+WHILE i < n REPEAT
+    IF scale != 1 THEN
+        LET scale = 2
+    ENDIF
+    LET total = total + rate * 12 / scale
+    PRINT rate * 12 + i
+    LET i = i + 1
+ENDWHILE
+PRINT total
+PRINT total * rate + total * rate
+LET area = rate * n
+PRINT area
+PRINT rate * n - 1
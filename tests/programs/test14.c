@@ -0,0 +1,42 @@
+#include <stdio.h>
+
+int main(void) {
+    float rate;
+    float n;
+    float i;
+    float total;
+    float area;
+    float t_2;
+    float t_3;
+    float t_8;
+    float t_11;
+
+    if (0 == scanf("%f", &rate)) {
+        rate = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &n)) {
+        n = 0;
+        scanf("%*s");
+    }
+    i = 0;
+    total = 0;
+    t_2 = rate * 12;
+    t_3 = t_2 / 1.0f;
+while_0:
+    if (!(i < n)) goto endwhile_1;
+    total = total + t_3;
+    printf("%.2f\n", (float)(t_2 + i));
+    i = i + 1;
+    goto while_0;
+endwhile_1:
+    printf("%.2f\n", (float)(total));
+    t_8 = total * rate;
+    printf("%.2f\n", (float)(t_8 + t_8));
+    t_11 = rate * n;
+    area = t_11;
+    printf("%.2f\n", (float)(area));
+    printf("%.2f\n", (float)(t_11 - 1));
+    
+    return 0;
+}
@@ -0,0 +1,10 @@
+# Constant arithmetic without a finite result.
+
+LET b = 1.0 / 0
+LET c = 1e300 * 1e300
+LET e = 1e300
+PRINT b
+PRINT c
+PRINT e
+PRINT -c
+PRINT c - c
@@ -0,0 +1,17 @@
+#include <math.h>
+#include <stdio.h>
+
+int main(void) {
+    float b;
+    float c;
+
+    b = 1.0 / 0;
+    c = 1e300 * 1e300;
+    printf("%.2f\n", (float)(b));
+    printf("%.2f\n", (float)(c));
+    printf("%.2f\n", (float)(INFINITY));
+    printf("%.2f\n", (float)(-c));
+    printf("%.2f\n", (float)(c - c));
+    
+    return 0;
+}
@@ -0,0 +1,34 @@
+# Comparisons, including with NaN, and mixed int/float/double arithmetic.
+
+INPUT a
+INPUT b
+IF a < b THEN
+    PRINT "a < b"
+ENDIF
+IF a <= b THEN
+    PRINT "a <= b"
+ENDIF
+IF a > b THEN
+    PRINT "a > b"
+ENDIF
+IF a >= b THEN
+    PRINT "a >= b"
+ENDIF
+IF a == b THEN
+    PRINT "a == b"
+ENDIF
+IF a != b THEN
+    PRINT "a != b"
+ENDIF
+IF a < b < 1 THEN
+    PRINT "chained"
+ENDIF
+LET c = a - b
+IF c != 0 THEN
+    PRINT "a - b is not zero"
+ENDIF
+PRINT -a
+PRINT a / 3
+PRINT a / 3.0
+PRINT 1 / 3 + a
+PRINT b * 0.1 - 1
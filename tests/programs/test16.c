@@ -0,0 +1,50 @@
+#include <stdio.h>
+
+int main(void) {
+    float a;
+    float b;
+    float c;
+    int t_0;
+
+    if (0 == scanf("%f", &a)) {
+        a = 0;
+        scanf("%*s");
+    }
+    if (0 == scanf("%f", &b)) {
+        b = 0;
+        scanf("%*s");
+    }
+    t_0 = a < b;
+    if (!(t_0)) goto endif_0;
+    printf("a < b\n");
+endif_0:
+    if (!(a <= b)) goto endif_1;
+    printf("a <= b\n");
+endif_1:
+    if (!(a > b)) goto endif_2;
+    printf("a > b\n");
+endif_2:
+    if (!(a >= b)) goto endif_3;
+    printf("a >= b\n");
+endif_3:
+    if (!(a == b)) goto endif_4;
+    printf("a == b\n");
+endif_4:
+    if (!(a != b)) goto endif_5;
+    printf("a != b\n");
+endif_5:
+    if (!(t_0 < 1)) goto endif_6;
+    printf("chained\n");
+endif_6:
+    c = a - b;
+    if (!(c != 0)) goto endif_7;
+    printf("a - b is not zero\n");
+endif_7:
+    printf("%.2f\n", (float)(-a));
+    printf("%.2f\n", (float)(a / 3));
+    printf("%.2f\n", (float)(a / 3.0));
+    printf("%.2f\n", (float)(0 + a));
+    printf("%.2f\n", (float)(b * 0.1 - 1));
+    
+    return 0;
+}
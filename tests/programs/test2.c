@@ -0,0 +1,10 @@
+#include <stdio.h>
+
+int main(void) {
+
+loop:
+    printf("hello, world!\n");
+    goto loop;
+    
+    return 0;
+}
@@ -0,0 +1,28 @@
+#include <stdio.h>
+
+int main(void) {
+    float nums;
+    float a;
+    float b;
+    float c;
+
+    printf("How many fibonacci numbers do you want?\n");
+    if (0 == scanf("%f", &nums)) {
+        nums = 0;
+        scanf("%*s");
+    }
+    printf("\n");
+    a = 0;
+    b = 1;
+while_0:
+    if (!(nums > 0)) goto endwhile_1;
+    printf("%.2f\n", (float)(a));
+    c = a + b;
+    a = b;
+    b = c;
+    nums = nums - 1;
+    goto while_0;
+endwhile_1:
+    
+    return 0;
+}
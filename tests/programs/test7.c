@@ -0,0 +1,36 @@
+#include <stdio.h>
+
+int main(void) {
+    float a;
+    float b;
+    float s;
+    float c;
+
+    a = 0;
+while_0:
+    if (!(a < 1)) goto endwhile_1;
+    printf("Enter number of scores: \n");
+    if (0 == scanf("%f", &a)) {
+        a = 0;
+        scanf("%*s");
+    }
+    goto while_0;
+endwhile_1:
+    b = 0;
+    s = 0;
+    printf("Enter one value at a time: \n");
+while_2:
+    if (!(b < a)) goto endwhile_3;
+    if (0 == scanf("%f", &c)) {
+        c = 0;
+        scanf("%*s");
+    }
+    s = s + c;
+    b = b + 1;
+    goto while_2;
+endwhile_3:
+    printf("Average: \n");
+    printf("%.2f\n", (float)(s / a));
+    
+    return 0;
+}
@@ -0,0 +1,8 @@
+#include <stdio.h>
+
+int main(void) {
+
+    printf("%.2f\n", (float)(5040.0f));
+    
+    return 0;
+}
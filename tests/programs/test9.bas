@@ -0,0 +1,5 @@
+# Numeric literal forms.
+LET a = 1e3
+LET b = .5
+LET c = &HFF + &B1010 + &o17 - 2.5E-1
+PRINT a * b + c